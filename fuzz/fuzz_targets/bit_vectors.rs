@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raid_ii_emulation::hamming_encoding::*;
+
+fuzz_target!(|data: &[u8]| {
+    let bits = bit_vector_from_bytes(data);
+    assert_eq!(bits.len(), data.len() * 8);
+    assert_eq!(bit_vector_to_bytes(&bits), data);
+
+    // Bit vectors of any length, the last byte is padded with zeros
+    let length = data.first().map_or(0, |byte| *byte as usize % 8);
+    let bits = &bits[..bits.len().saturating_sub(length)];
+    let bytes = bit_vector_to_bytes(bits);
+    assert_eq!(bytes.len(), bits.len().div_ceil(8));
+    let padded = bit_vector_from_bytes(&bytes);
+    assert_eq!(&padded[..bits.len()], bits);
+    assert!(padded[bits.len()..].iter().all(|bit| !bit));
+});
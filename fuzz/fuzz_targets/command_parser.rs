@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raid_ii_emulation::commands::{parse, ParseError};
+use raid_ii_emulation::tokenizer::tokenize;
+
+fuzz_target!(|line: &str| {
+    // The parser never panics, and fails on the lines the tokenizer rejects
+    match tokenize(line) {
+        Ok(_) => {
+            let _ = parse(line);
+        }
+        Err(error) => assert_eq!(parse(line), Err(ParseError::Tokenize(error))),
+    }
+});
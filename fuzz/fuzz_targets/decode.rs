@@ -0,0 +1,34 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raid_ii_emulation::hamming_encoding::*;
+
+fuzz_target!(|data: &[u8]| {
+    // Any length, not only the length of a codeword
+    let mut bits: Vec<Bit> = data.iter().map(|byte| byte & 1 == 1).collect();
+    let received = bits.clone();
+    match decode(&mut bits) {
+        HammingDecodeResult::NoError { .. } => assert_eq!(bits, received),
+        HammingDecodeResult::OneError { position, .. } => {
+            assert!(position < bits.len());
+            let changed: Vec<usize> = (0..bits.len())
+                .filter(|index| bits[*index] != received[*index])
+                .collect();
+            assert_eq!(changed, vec![position]);
+        }
+        HammingDecodeResult::DoubleError => assert_eq!(bits, received),
+    }
+
+    // The same bits through every code, wrong lengths must be errors
+    for (data_bits, extended) in [(4, false), (4, true), (8, false), (8, true), (64, true)] {
+        let code = HammingCode::with_data_bits(data_bits, extended).unwrap();
+        match code.decode(&received) {
+            Ok(HammingDecodeResult::NoError { decoded_bits })
+            | Ok(HammingDecodeResult::OneError { decoded_bits, .. }) => {
+                assert_eq!(decoded_bits.len(), data_bits)
+            }
+            Ok(HammingDecodeResult::DoubleError) => {}
+            Err(_) => assert_ne!(received.len(), code.codeword_len()),
+        }
+    }
+});
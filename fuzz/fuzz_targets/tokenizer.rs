@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raid_ii_emulation::tokenizer::*;
+
+fuzz_target!(|line: &str| {
+    let Ok(tokens) = tokenize(line) else {
+        return;
+    };
+
+    // What `escape_bytes` prints can be typed back as the same word
+    for token in tokens {
+        if let Token::Word(word) = token {
+            let quoted = format!("\"{}\"", escape_bytes(&word));
+            assert_eq!(tokenize(&quoted), Ok(vec![Token::Word(word)]));
+        }
+    }
+});
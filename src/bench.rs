@@ -0,0 +1,167 @@
+use crate::raid::RaidII;
+use crate::timing::DiskTiming;
+use std::fmt;
+use std::time::Duration;
+
+// Every layout stores as much data as RAID II: 8 disks of data
+const DATA_DISKS: u64 = 8;
+
+// Block interleaved layouts put this many bytes on one disk before going to the next
+pub const STRIPE_UNIT: u64 = 64 * 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    // Block interleaved, no redundancy
+    Raid0,
+    // Every disk of RAID 0 has a mirror
+    Raid1,
+    // Bit interleaved with Hamming code disks, this emulator
+    Raid2,
+    // Byte interleaved with a parity disk
+    Raid3,
+    // Block interleaved with parity spread over all disks
+    Raid5,
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Layout::Raid0 => "RAID 0",
+            Layout::Raid1 => "RAID 1",
+            Layout::Raid2 => "RAID 2",
+            Layout::Raid3 => "RAID 3",
+            Layout::Raid5 => "RAID 5",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Performance {
+    // Time of a single request on an idle array
+    pub latency: Duration,
+    // Bytes per second when there are always enough requests waiting
+    pub throughput: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutResult {
+    pub layout: Layout,
+    pub disks: u64,
+    pub read: Performance,
+    pub write: Performance,
+}
+
+/// Compares the layouts on requests of `request_size` bytes at random places.
+///
+/// RAID 2 is timed by the emulated array, so the disk timings set on it are taken into
+/// account. The other layouts are built from disks with `timing`. Requests which need
+/// different disks are served at the same time, which is what makes block interleaved
+/// layouts better than RAID 2 on small requests.
+pub fn compare(raid: &RaidII, timing: &DiskTiming, request_size: u64) -> Vec<LayoutResult> {
+    // Disks touched by a request of the block interleaved layouts
+    let touched = request_size.div_ceil(STRIPE_UNIT).clamp(1, DATA_DISKS);
+    let block_access = timing.access_time(request_size.div_ceil(touched));
+    let byte_access = timing.access_time(request_size.div_ceil(DATA_DISKS));
+    let raid2_access = raid.access_time(request_size as usize);
+
+    let raid5_write = if touched == DATA_DISKS {
+        // The whole stripe is written, the parity is computed from the new data
+        performance(request_size, block_access, touched + 1, DATA_DISKS + 1)
+    } else {
+        // Old data and parity are read, and written one rotation later
+        performance(
+            request_size,
+            block_access + timing.rotation_time(),
+            touched + 1,
+            DATA_DISKS + 1,
+        )
+    };
+    let raid2_disks = raid.total_disks() as u64;
+
+    vec![
+        LayoutResult {
+            layout: Layout::Raid0,
+            disks: DATA_DISKS,
+            read: performance(request_size, block_access, touched, DATA_DISKS),
+            write: performance(request_size, block_access, touched, DATA_DISKS),
+        },
+        LayoutResult {
+            layout: Layout::Raid1,
+            disks: 2 * DATA_DISKS,
+            // Either of the mirrors can serve a read
+            read: performance(request_size, block_access, touched, 2 * DATA_DISKS),
+            write: performance(request_size, block_access, 2 * touched, 2 * DATA_DISKS),
+        },
+        LayoutResult {
+            layout: Layout::Raid2,
+            disks: raid2_disks,
+            read: performance(request_size, raid2_access, raid2_disks, raid2_disks),
+            write: performance(request_size, raid2_access, raid2_disks, raid2_disks),
+        },
+        LayoutResult {
+            layout: Layout::Raid3,
+            disks: DATA_DISKS + 1,
+            read: performance(request_size, byte_access, DATA_DISKS + 1, DATA_DISKS + 1),
+            write: performance(request_size, byte_access, DATA_DISKS + 1, DATA_DISKS + 1),
+        },
+        LayoutResult {
+            layout: Layout::Raid5,
+            disks: DATA_DISKS + 1,
+            read: performance(request_size, block_access, touched, DATA_DISKS + 1),
+            write: raid5_write,
+        },
+    ]
+}
+
+// `busy_disks` of `total_disks` work on one request for `latency`
+fn performance(
+    request_size: u64,
+    latency: Duration,
+    busy_disks: u64,
+    total_disks: u64,
+) -> Performance {
+    let parallel_requests = (total_disks / busy_disks).max(1);
+    Performance {
+        latency,
+        throughput: (parallel_requests * request_size) as f64 / latency.as_secs_f64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench::*;
+
+    fn result(results: &[LayoutResult], layout: Layout) -> &LayoutResult {
+        results
+            .iter()
+            .find(|result| result.layout == layout)
+            .unwrap()
+    }
+
+    #[test]
+    fn small_requests_test() {
+        let raid_ii = RaidII::from_data_capacity(1024);
+        let results = compare(&raid_ii, &DiskTiming::default(), 4096);
+        let raid2 = result(&results, Layout::Raid2);
+        let raid5 = result(&results, Layout::Raid5);
+        // A small read keeps all 13 disks busy, RAID 5 serves 9 of them at once
+        assert!(raid5.read.throughput > 8.0 * raid2.read.throughput);
+        // The read-modify-write of RAID 5 still leaves it ahead
+        assert!(raid5.write.throughput > raid2.write.throughput);
+        assert!(raid5.write.latency > raid5.read.latency);
+    }
+
+    #[test]
+    fn large_requests_test() {
+        let raid_ii = RaidII::from_data_capacity(1024);
+        let results = compare(&raid_ii, &DiskTiming::default(), 1024 * 1024);
+        let raid0 = result(&results, Layout::Raid0);
+        let raid2 = result(&results, Layout::Raid2);
+        let raid3 = result(&results, Layout::Raid3);
+        // Every layout uses all its data disks, RAID 2 keeps up
+        assert_eq!(raid2.read, raid3.read);
+        assert_eq!(raid2.read, raid0.read);
+        assert_eq!(raid2.disks, 13);
+    }
+}
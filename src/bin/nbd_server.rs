@@ -0,0 +1,31 @@
+use raid_ii_emulation::nbd::{self, DEFAULT_ADDRESS};
+use raid_ii_emulation::raid::RaidII;
+use std::net::TcpListener;
+
+// Usage: nbd_server [address] [--sec]
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let extended = !arguments.iter().any(|argument| argument == "--sec");
+    let address = arguments
+        .iter()
+        .find(|argument| !argument.starts_with("--"))
+        .map_or(DEFAULT_ADDRESS, String::as_str);
+
+    let mut raid = RaidII::new(1024, extended);
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("Can not listen on {}: {}", address, error);
+            return;
+        }
+    };
+    println!(
+        "Serving {} blocks of {} bytes on {}.",
+        raid.block_count(),
+        raid.block_size(),
+        address
+    );
+    if let Err(error) = nbd::serve(&mut raid, &listener) {
+        println!("The server stopped: {}", error);
+    }
+}
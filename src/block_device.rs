@@ -0,0 +1,103 @@
+use crate::raid::RaidII;
+use std::fmt;
+
+/// Storage addressed by fixed-size blocks, numbered by their logical block address.
+///
+/// For `RaidII` the blocks and the files are separate regions of one address space: blocks
+/// which hold file bytes can not be read or written, and files are not stored over blocks
+/// written by `write_block`.
+pub trait BlockDevice {
+    fn block_size(&self) -> usize;
+
+    fn block_count(&self) -> u64;
+
+    /// `buf` must be exactly one block long
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+
+    /// `buf` must be exactly one block long
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError>;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockError {
+    OutOfRange { lba: u64, block_count: u64 },
+    WrongBufferSize { expected: usize, actual: usize },
+    // The stripe at the position can not be decoded
+    Uncorrectable { lba: u64, position: usize },
+    // The block holds bytes of files
+    FileSpace { lba: u64 },
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::OutOfRange { lba, block_count } => write!(
+                f,
+                "block {} is out of range, the device has {} blocks",
+                lba, block_count
+            ),
+            BlockError::WrongBufferSize { expected, actual } => {
+                write!(f, "expected a buffer of {} bytes, got {}", expected, actual)
+            }
+            BlockError::Uncorrectable { lba, position } => write!(
+                f,
+                "block {} can not be decoded, stripe {} is corrupted",
+                lba, position
+            ),
+            BlockError::FileSpace { lba } => write!(f, "block {} is used by files", lba),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+impl BlockDevice for RaidII {
+    fn block_size(&self) -> usize {
+        RaidII::block_size(self)
+    }
+
+    fn block_count(&self) -> u64 {
+        RaidII::block_count(self)
+    }
+
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        RaidII::read_block(self, lba, buf)
+    }
+
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        RaidII::write_block(self, lba, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block_device::*;
+    use crate::raid::*;
+
+    // Works with any block device
+    fn copy_block(device: &mut dyn BlockDevice, from: u64, to: u64) -> Result<(), BlockError> {
+        let mut block = vec![0; device.block_size()];
+        device.read_block(from, &mut block)?;
+        device.write_block(to, &block)
+    }
+
+    #[test]
+    fn block_device_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let block: Vec<u8> = (0..DEFAULT_BLOCK_SIZE).map(|i| i as u8).collect();
+        raid_ii.write_block(3, &block).unwrap();
+        copy_block(&mut raid_ii, 3, 5).unwrap();
+
+        let mut read = vec![0; DEFAULT_BLOCK_SIZE];
+        raid_ii.read_block(5, &mut read).unwrap();
+        assert_eq!(read, block);
+        assert_eq!(
+            BlockError::OutOfRange {
+                lba: 7,
+                block_count: 6
+            }
+            .to_string(),
+            "block 7 is out of range, the device has 6 blocks"
+        );
+    }
+}
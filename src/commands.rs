@@ -0,0 +1,794 @@
+use crate::bench;
+use crate::coverage;
+use crate::hamming_encoding::{Bit, HammingCode};
+use crate::raid::{DeleteError, FileReadResult, FileType, FileWriteResult, RaidII};
+use crate::tokenizer::{escape_bytes, tokenize, Token, TokenizeError};
+use std::fmt::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Data {
+    Inline(Vec<u8>),
+    // The data follows on the next lines of the input, up to a line equal to the tag.
+    // The caller reads them and replaces it with `Inline` before `execute`.
+    Heredoc(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Write {
+        data: Data,
+        name: String,
+    },
+    Read {
+        name: String,
+    },
+    Delete {
+        name: String,
+    },
+    Corrupt {
+        disk_number: usize,
+    },
+    Fail {
+        disk_number: usize,
+    },
+    Replace {
+        disk_number: usize,
+    },
+    Status,
+    Spares {
+        count: usize,
+    },
+    // Fraction of a rotation
+    Desync {
+        disk_number: usize,
+        offset: f64,
+    },
+    Sync,
+    // None for the code of the array
+    Coverage {
+        code: Option<HammingCode>,
+        max_errors: usize,
+    },
+    Bench,
+    Ls,
+    Stat {
+        name: String,
+    },
+    Checksum {
+        enabled: bool,
+    },
+    // None for the whole log
+    Log {
+        count: Option<usize>,
+    },
+    Logfile {
+        path: PathBuf,
+    },
+    Snapshot,
+    Restore {
+        id: usize,
+    },
+    Undo,
+    Redo,
+    // None to finish the reshape at once
+    Reshape {
+        data_disks: usize,
+        bytes: Option<usize>,
+    },
+    Exit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    Tokenize(TokenizeError),
+    Empty,
+    UnknownCommand(String),
+    // The command name is known, its arguments are wrong
+    InvalidArguments(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Tokenize(error) => write!(f, "{}", error),
+            ParseError::Empty => write!(f, "the command is empty"),
+            ParseError::UnknownCommand(name) => write!(f, "unknown command '{}'", name),
+            ParseError::InvalidArguments(name) => write!(f, "wrong arguments of '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Output {
+    // Lines to print, empty when the command has nothing to say
+    Text(String),
+    // The command can not be applied to this array, a disk number out of range for example
+    Invalid,
+    Exit,
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Text(text) => write!(f, "{}", text),
+            Output::Invalid => write!(f, "Invalid command. Try again, please!"),
+            Output::Exit => Ok(()),
+        }
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    let tokens = tokenize(line).map_err(ParseError::Tokenize)?;
+    let words: Vec<String> = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(word) => String::from_utf8_lossy(word).into_owned(),
+            Token::Heredoc(tag) => format!("<<{}", tag),
+        })
+        .collect();
+    let Some(name) = words.first() else {
+        return Err(ParseError::Empty);
+    };
+    let invalid = || ParseError::InvalidArguments(name.clone());
+    let disk_number = || match words.as_slice() {
+        [_, number] => number.parse::<usize>().map_err(|_| invalid()),
+        _ => Err(invalid()),
+    };
+
+    let command = match name.as_str() {
+        "write" => {
+            let [_, data, Token::Word(file_name)] = tokens.as_slice() else {
+                return Err(invalid());
+            };
+            let data = match data {
+                Token::Word(word) => Data::Inline(word.clone()),
+                Token::Heredoc(tag) => Data::Heredoc(tag.clone()),
+            };
+            let name = String::from_utf8(file_name.clone()).map_err(|_| invalid())?;
+            Command::Write { data, name }
+        }
+        "read" | "stat" | "delete" => {
+            let [_, file_name] = words.as_slice() else {
+                return Err(invalid());
+            };
+            let name = file_name.clone();
+            match words[0].as_str() {
+                "read" => Command::Read { name },
+                "stat" => Command::Stat { name },
+                _ => Command::Delete { name },
+            }
+        }
+        "corrupt" => Command::Corrupt {
+            disk_number: disk_number()?,
+        },
+        "fail" => Command::Fail {
+            disk_number: disk_number()?,
+        },
+        "replace" => Command::Replace {
+            disk_number: disk_number()?,
+        },
+        "desync" => {
+            if !(2..=3).contains(&words.len()) {
+                return Err(invalid());
+            }
+            let disk_number = words[1].parse::<usize>().map_err(|_| invalid())?;
+            // The spindle is half a rotation off unless told otherwise
+            let offset = match words.get(2) {
+                None => 0.5,
+                Some(word) => word.parse::<f64>().map_err(|_| invalid())?,
+            };
+            Command::Desync {
+                disk_number,
+                offset,
+            }
+        }
+        "coverage" => {
+            if words.len() > 3 {
+                return Err(invalid());
+            }
+            let code = match words.get(1).map(String::as_str) {
+                None | Some("array") => None,
+                Some(code_name) => Some(
+                    coverage::named_codes()
+                        .into_iter()
+                        .find(|(name, _)| *name == code_name)
+                        .map(|(_, code)| code)
+                        .ok_or_else(invalid)?,
+                ),
+            };
+            let max_errors = match words.get(2) {
+                None => coverage::MAX_COVERAGE_ERRORS,
+                Some(word) => word.parse::<usize>().map_err(|_| invalid())?,
+            };
+            if max_errors > coverage::MAX_COVERAGE_ERRORS {
+                return Err(invalid());
+            }
+            Command::Coverage { code, max_errors }
+        }
+        "log" => match words.as_slice() {
+            [_] => Command::Log { count: None },
+            [_, count] => Command::Log {
+                count: Some(count.parse::<usize>().map_err(|_| invalid())?),
+            },
+            _ => return Err(invalid()),
+        },
+        "logfile" => match words.as_slice() {
+            [_, path] => Command::Logfile {
+                path: PathBuf::from(path),
+            },
+            _ => return Err(invalid()),
+        },
+        "checksum" => match words.as_slice() {
+            [_, state] if state == "on" => Command::Checksum { enabled: true },
+            [_, state] if state == "off" => Command::Checksum { enabled: false },
+            _ => return Err(invalid()),
+        },
+        "spares" => match words.as_slice() {
+            [_, count] => Command::Spares {
+                count: count.parse::<usize>().map_err(|_| invalid())?,
+            },
+            _ => return Err(invalid()),
+        },
+        "restore" => match words.as_slice() {
+            [_, id] => Command::Restore {
+                id: id.parse::<usize>().map_err(|_| invalid())?,
+            },
+            _ => return Err(invalid()),
+        },
+        "reshape" => match words.as_slice() {
+            [_, data_disks] => Command::Reshape {
+                data_disks: data_disks.parse::<usize>().map_err(|_| invalid())?,
+                bytes: None,
+            },
+            [_, data_disks, bytes] => Command::Reshape {
+                data_disks: data_disks.parse::<usize>().map_err(|_| invalid())?,
+                bytes: Some(bytes.parse::<usize>().map_err(|_| invalid())?),
+            },
+            _ => return Err(invalid()),
+        },
+        // Anything after `exit` is ignored
+        "exit" => Command::Exit,
+        "status" | "sync" | "bench" | "ls" | "snapshot" | "undo" | "redo" => {
+            if words.len() != 1 {
+                return Err(invalid());
+            }
+            match name.as_str() {
+                "status" => Command::Status,
+                "sync" => Command::Sync,
+                "bench" => Command::Bench,
+                "snapshot" => Command::Snapshot,
+                "undo" => Command::Undo,
+                "redo" => Command::Redo,
+                _ => Command::Ls,
+            }
+        }
+        _ => return Err(ParseError::UnknownCommand(name.clone())),
+    };
+    Ok(command)
+}
+
+/// Runs the command on the array. Writes, deletes, corruptions, failures and replacements
+/// of disks are journaled, so `undo` and `redo` go through them one at a time.
+pub fn execute(raid: &mut RaidII, command: Command) -> Output {
+    // Writing to a String can not fail
+    let mut text = String::new();
+    match command {
+        Command::Write {
+            data: Data::Inline(data),
+            name,
+        } => {
+            let (result, elapsed) = raid.journaled(&format!("write {}", name), |raid| {
+                raid.write_file_timed(&data, FileType::Text, &name)
+            });
+            match result {
+                FileWriteResult::Success => write!(
+                    text,
+                    "File {} with content \"{}\" has been written! (simulated time: {})",
+                    name,
+                    escape_bytes(&data),
+                    format_duration(elapsed)
+                ),
+                FileWriteResult::NotEnoughSpace => write!(text, "Not enough space to store data!"),
+            }
+            .unwrap();
+        }
+        Command::Write {
+            data: Data::Heredoc(_),
+            ..
+        } => return Output::Invalid,
+        Command::Read { name } => {
+            let (result, elapsed) = raid.read_file_timed(&name);
+            match result {
+                FileReadResult::NotFound => write!(text, "File {} does not exist", name),
+                FileReadResult::DisksCorrupted => write!(
+                    text,
+                    "All data is corrupted. Failed to complete you request! Two disks are corrupted so there is no way to restore data :("
+                ),
+                FileReadResult::SilentCorruptionDetected => write!(
+                    text,
+                    "Data of {} does not match its checksum: more disks are damaged than the code can correct",
+                    name
+                ),
+                FileReadResult::Success(file_type, byte_data) => {
+                    match file_type {
+                        FileType::Text => match String::from_utf8(byte_data) {
+                            Ok(content_string) => {
+                                writeln!(text, "File: {}\nContent: {}", name, content_string)
+                            }
+                            Err(error) => writeln!(
+                                text,
+                                "File: {}\nContent (not UTF-8): \"{}\"",
+                                name,
+                                escape_bytes(error.as_bytes())
+                            ),
+                        },
+                    }
+                    .unwrap();
+                    write!(text, "Simulated time: {}", format_duration(elapsed))
+                }
+            }
+            .unwrap();
+        }
+        Command::Delete { name } => {
+            match raid.journaled(&format!("delete {}", name), |raid| raid.delete_file(&name)) {
+                Ok(()) => write!(text, "File {} has been deleted", name),
+                Err(DeleteError::NotFound) => return Output::Invalid,
+                Err(error) => write!(text, "Can not delete {}: {}", name, error),
+            }
+            .unwrap();
+        }
+        Command::Corrupt { disk_number } => {
+            let operation = format!("corrupt {}", disk_number);
+            if !raid.journaled(&operation, |raid| raid.corrupt_disk(disk_number)) {
+                return Output::Invalid;
+            }
+        }
+        Command::Fail { disk_number } => {
+            let operation = format!("fail {}", disk_number);
+            if !raid.journaled(&operation, |raid| raid.fail_disk(disk_number)) {
+                return Output::Invalid;
+            }
+        }
+        Command::Replace { disk_number } => {
+            let mut progress = None;
+            let replaced = raid.journaled(&format!("replace {}", disk_number), |raid| {
+                if !raid.replace_disk(disk_number) {
+                    return false;
+                }
+                progress = raid.rebuild();
+                true
+            });
+            if !replaced {
+                return Output::Invalid;
+            }
+            if let Some(progress) = progress {
+                write!(
+                    text,
+                    "Disk {} has been rebuilt, {} of {} positions could not be restored",
+                    disk_number, progress.lost_positions, progress.total_positions
+                )
+                .unwrap();
+            }
+        }
+        Command::Status => format_status(&mut text, raid).unwrap(),
+        Command::Spares { count } => {
+            raid.set_hot_spares(count);
+            write!(text, "Hot spares: {}", raid.hot_spares()).unwrap();
+            if let Some(progress) = raid.status().rebuild {
+                write!(
+                    text,
+                    ", disk {} is being rebuilt on a spare",
+                    progress.disk_number
+                )
+                .unwrap();
+            }
+        }
+        Command::Desync {
+            disk_number,
+            offset,
+        } => {
+            if !raid.desync_disk(disk_number, offset) {
+                return Output::Invalid;
+            }
+            write!(
+                text,
+                "Disk {} spins {:.2} of a rotation ahead, try to read a file now. Average 4 KiB access: {}",
+                disk_number,
+                offset.rem_euclid(1.0),
+                format_duration(raid.access_time(4096))
+            )
+            .unwrap();
+        }
+        Command::Sync => {
+            raid.sync_spindles();
+            write!(
+                text,
+                "All spindles are in sync. Average 4 KiB access: {}",
+                format_duration(raid.access_time(4096))
+            )
+            .unwrap();
+        }
+        Command::Coverage { code, max_errors } => {
+            let code = code.unwrap_or_else(|| raid.code());
+            format_coverage(&mut text, &code, max_errors).unwrap();
+        }
+        Command::Bench => format_bench(&mut text, raid).unwrap(),
+        Command::Ls => {
+            writeln!(
+                text,
+                "{:<20} {:>8} {:>8} {:>8}  type",
+                "name", "size", "start", "end"
+            )
+            .unwrap();
+            for file in raid.files() {
+                writeln!(
+                    text,
+                    "{:<20} {:>8} {:>8} {:>8}  {:?}",
+                    escape_bytes(file.name().as_bytes()),
+                    file.size(),
+                    file.start_pos(),
+                    file.end_pos(),
+                    file.file_type()
+                )
+                .unwrap();
+            }
+            write!(text, "Free space: {}", raid.free_space()).unwrap();
+        }
+        Command::Stat { name } => {
+            match (
+                raid.files().find(|file| file.name() == name),
+                raid.file_health(&name),
+            ) {
+                (Some(file), Some(health)) => write!(
+                    text,
+                    "File: {}\nType: {:?}\nSize: {}\nPositions: {}..{}\nChecksum: {}\nClean: {}\nCorrectable errors: {}\nUncorrectable: {}",
+                    name,
+                    file.file_type(),
+                    file.size(),
+                    file.start_pos(),
+                    file.end_pos(),
+                    file.checksum().map_or("none".to_owned(), |checksum| format!("{:08x}", checksum)),
+                    health.clean,
+                    health.correctable,
+                    health.uncorrectable
+                ),
+                _ => write!(text, "File {} does not exist", name),
+            }
+            .unwrap();
+        }
+        Command::Checksum { enabled } => raid.set_checksums(enabled),
+        Command::Log { count } => {
+            let events = raid.events();
+            let count = count.unwrap_or(events.len()).min(events.len());
+            let lines: Vec<String> = events[events.len() - count..]
+                .iter()
+                .map(|event| event.to_string())
+                .collect();
+            text = lines.join("\n");
+        }
+        Command::Logfile { path } => match raid.set_event_sink(&path) {
+            Ok(()) => write!(text, "Events are written to {}", path.display()),
+            Err(error) => write!(text, "Can not open {}: {}", path.display(), error),
+        }
+        .unwrap(),
+        Command::Snapshot => {
+            let id = raid.snapshot();
+            write!(
+                text,
+                "Snapshot {} taken, go back to it with 'restore {}'",
+                id, id
+            )
+            .unwrap();
+        }
+        Command::Restore { id } => {
+            if !raid.restore(id) {
+                return Output::Invalid;
+            }
+            write!(text, "Snapshot {} restored", id).unwrap();
+        }
+        Command::Undo => match raid.undo() {
+            Some(operation) => write!(text, "Undone: {}", operation).unwrap(),
+            None => write!(text, "Nothing to undo").unwrap(),
+        },
+        Command::Redo => match raid.redo() {
+            Some(operation) => write!(text, "Redone: {}", operation).unwrap(),
+            None => write!(text, "Nothing to redo").unwrap(),
+        },
+        Command::Reshape { data_disks, bytes } => {
+            let result = match bytes {
+                None => raid.reshape(data_disks),
+                Some(bytes) => raid
+                    .start_reshape(data_disks)
+                    .and_then(|progress| Ok(raid.reshape_step(bytes)?.unwrap_or(progress))),
+            };
+            match result {
+                Err(error) => write!(text, "Can not reshape the array: {}", error),
+                Ok(progress) if raid.reshape_progress().is_some() => write!(
+                    text,
+                    "Reshaping to {} data disks: {} of {} bytes, continue with 'reshape {}'",
+                    data_disks, progress.reshaped_bytes, progress.total_bytes, data_disks
+                ),
+                Ok(_) => write!(
+                    text,
+                    "The array has {} data disks and {} disks in total now",
+                    data_disks,
+                    raid.total_disks()
+                ),
+            }
+            .unwrap();
+        }
+        Command::Exit => return Output::Exit,
+    }
+    Output::Text(text)
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn format_status(text: &mut String, raid: &RaidII) -> fmt::Result {
+    let status = raid.status();
+    writeln!(text, "Array: {:?}", status.state)?;
+    writeln!(text, "Hot spares: {}", status.hot_spares)?;
+    if let Some(progress) = status.rebuild {
+        writeln!(
+            text,
+            "Rebuilding disk {}: {} of {} positions",
+            progress.disk_number, progress.rebuilt_positions, progress.total_positions
+        )?;
+    }
+    if let Some(progress) = status.reshape {
+        writeln!(
+            text,
+            "Reshaping from {} to {} data disks: {} of {} bytes",
+            progress.from_data_disks,
+            progress.to_data_disks,
+            progress.reshaped_bytes,
+            progress.total_bytes
+        )?;
+    }
+    for (index, disk) in status.disks.iter().enumerate() {
+        writeln!(
+            text,
+            "Disk {:>2}: {:<9} corrected errors: {:<6} spindle offset: {:.2}",
+            index + 1,
+            format!("{:?}", disk.state),
+            disk.corrected_errors,
+            raid.spindle_offset(index + 1).unwrap_or(0.0)
+        )?;
+    }
+    write!(
+        text,
+        "Simulated time: {}",
+        format_duration(raid.simulated_time())
+    )
+}
+
+fn format_bench(text: &mut String, raid: &RaidII) -> fmt::Result {
+    let timing = raid.disk_timing(1).unwrap_or_default();
+    for (title, request_size) in [
+        ("Small requests, 4 KiB", 4096),
+        ("Large requests, 1 MiB", 1 << 20),
+    ] {
+        writeln!(text, "{} at random places:", title)?;
+        writeln!(
+            text,
+            "{:<8} {:>5} {:>12} {:>12} {:>12} {:>12}",
+            "layout", "disks", "read", "read MB/s", "write", "write MB/s"
+        )?;
+        for result in bench::compare(raid, &timing, request_size) {
+            writeln!(
+                text,
+                "{:<8} {:>5} {:>12} {:>12.2} {:>12} {:>12.2}",
+                result.layout.to_string(),
+                result.disks,
+                format_duration(result.read.latency),
+                result.read.throughput / 1e6,
+                format_duration(result.write.latency),
+                result.write.throughput / 1e6
+            )?;
+        }
+    }
+    write!(
+        text,
+        "Throughput is for a queue of requests, which run at the same time on different disks."
+    )
+}
+
+fn format_coverage(text: &mut String, code: &HammingCode, max_errors: usize) -> fmt::Result {
+    // Any data word gives the same table for a linear code
+    let data: Vec<Bit> = (0..code.data_bits()).map(|i| i % 2 == 0).collect();
+    match coverage::error_coverage(code, &data, max_errors) {
+        Ok(rows) => {
+            writeln!(
+                text,
+                "Hamming code ({}, {}){}:",
+                code.codeword_len(),
+                code.data_bits(),
+                if code.extended() { " SECDED" } else { " SEC" }
+            )?;
+            write!(text, "{}", coverage::COVERAGE_HEADER)?;
+            for row in rows {
+                write!(text, "\n{}", row)?;
+            }
+            Ok(())
+        }
+        Err(error) => write!(text, "Can not check the code: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            parse("write \"two words\" name"),
+            Ok(Command::Write {
+                data: Data::Inline(b"two words".to_vec()),
+                name: "name".to_owned()
+            })
+        );
+        assert_eq!(
+            parse("write <<EOF notes"),
+            Ok(Command::Write {
+                data: Data::Heredoc("EOF".to_owned()),
+                name: "notes".to_owned()
+            })
+        );
+        assert_eq!(
+            parse("  read  'a file' "),
+            Ok(Command::Read {
+                name: "a file".to_owned()
+            })
+        );
+        assert_eq!(parse("corrupt 3"), Ok(Command::Corrupt { disk_number: 3 }));
+        assert_eq!(
+            parse("reshape 16 100"),
+            Ok(Command::Reshape {
+                data_disks: 16,
+                bytes: Some(100)
+            })
+        );
+        assert_eq!(
+            parse("desync 2"),
+            Ok(Command::Desync {
+                disk_number: 2,
+                offset: 0.5
+            })
+        );
+        assert_eq!(
+            parse("coverage 7,4 2"),
+            Ok(Command::Coverage {
+                code: Some(HammingCode::hamming_7_4()),
+                max_errors: 2
+            })
+        );
+        assert_eq!(
+            parse("coverage"),
+            Ok(Command::Coverage {
+                code: None,
+                max_errors: 3
+            })
+        );
+        assert_eq!(parse("log"), Ok(Command::Log { count: None }));
+        assert_eq!(
+            parse("checksum off"),
+            Ok(Command::Checksum { enabled: false })
+        );
+        assert_eq!(parse("restore 2"), Ok(Command::Restore { id: 2 }));
+        assert_eq!(
+            parse("delete notes"),
+            Ok(Command::Delete {
+                name: "notes".to_owned()
+            })
+        );
+        assert_eq!(parse("undo"), Ok(Command::Undo));
+        assert_eq!(parse("spares 2"), Ok(Command::Spares { count: 2 }));
+        assert_eq!(parse("exit now"), Ok(Command::Exit));
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+        assert_eq!(
+            parse("write \"open"),
+            Err(ParseError::Tokenize(TokenizeError::UnterminatedQuote('"')))
+        );
+        assert_eq!(
+            parse("format c:"),
+            Err(ParseError::UnknownCommand("format".to_owned()))
+        );
+        for line in [
+            "write",
+            "write data",
+            "write data <<EOF",
+            "write data \"\\xff\"",
+            "read",
+            "corrupt",
+            "corrupt three",
+            "corrupt -1",
+            "fail 1 2",
+            "desync 1 half",
+            "coverage 9,5",
+            "coverage array many",
+            "coverage 127,120 6",
+            "log -1",
+            "checksum maybe",
+            "status now",
+            "restore",
+            "restore last",
+            "reshape",
+            "reshape 16 all",
+        ] {
+            assert!(
+                matches!(parse(line), Err(ParseError::InvalidArguments(_))),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn execute_test() {
+        let mut raid_ii = RaidII::new(64, true);
+        let mut run = |line: &str| execute(&mut raid_ii, parse(line).unwrap());
+
+        assert!(
+            matches!(run("write hello greeting"), Output::Text(text) if text.contains("has been written"))
+        );
+        assert_eq!(run("corrupt 3"), Output::Text(String::new()));
+        assert_eq!(run("corrupt 14"), Output::Invalid);
+        let Output::Text(text) = run("read greeting") else {
+            panic!("read has no output");
+        };
+        assert!(text.starts_with("File: greeting\nContent: hello\n"));
+        assert_eq!(
+            run("read missing"),
+            Output::Text("File missing does not exist".to_owned())
+        );
+        assert!(matches!(run("ls"), Output::Text(text) if text.lines().count() == 3));
+        assert_eq!(run("write <<EOF notes"), Output::Invalid);
+        assert!(matches!(run("coverage 7,4 1"), Output::Text(text) if text.lines().count() == 3));
+        assert!(
+            matches!(run("snapshot"), Output::Text(text) if text.starts_with("Snapshot 1 taken"))
+        );
+        run("corrupt 5");
+        assert_eq!(run("restore 2"), Output::Invalid);
+        assert_eq!(run("undo"), Output::Text("Undone: corrupt 5".to_owned()));
+        assert_eq!(run("redo"), Output::Text("Redone: corrupt 5".to_owned()));
+        assert_eq!(run("redo"), Output::Text("Nothing to redo".to_owned()));
+        assert_eq!(run("delete missing"), Output::Invalid);
+        assert_eq!(run("spares 1"), Output::Text("Hot spares: 1".to_owned()));
+        run("fail 2");
+        assert!(matches!(run("status"), Output::Text(text) if text.contains("Hot spares: 0")));
+        assert_eq!(
+            run("restore 1"),
+            Output::Text("Snapshot 1 restored".to_owned())
+        );
+        assert!(
+            matches!(run("reshape 12"), Output::Text(text) if text.starts_with("Can not reshape"))
+        );
+        assert_eq!(
+            run("reshape 16 1"),
+            Output::Text(
+                "Reshaping to 16 data disks: 2 of 5 bytes, continue with 'reshape 16'".to_owned()
+            )
+        );
+        assert!(
+            matches!(run("status"), Output::Text(text) if text.starts_with("Array: Reshaping"))
+        );
+        assert!(
+            matches!(run("reshape 16"), Output::Text(text) if text.starts_with("The array has 16 data disks and 22 disks"))
+        );
+        assert!(
+            matches!(run("read greeting"), Output::Text(text) if text.contains("Content: hello"))
+        );
+        assert_eq!(run("exit"), Output::Exit);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::hamming_encoding::*;
+use std::fmt;
+
+/// What the decoder made of every error pattern with the same number of flipped bits
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageRow {
+    pub errors: usize,
+    pub patterns: u64,
+    // The original data came back
+    pub corrected: u64,
+    // The decoder reported that it can not decode the codeword
+    pub detected: u64,
+    // The decoder "corrected" a bit and returned wrong data
+    pub miscorrected: u64,
+    // The decoder saw no error at all: the pattern turned the codeword into another one
+    pub undetected: u64,
+}
+
+impl fmt::Display for CoverageRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>6} {:>10} {:>10} {:>10} {:>12} {:>10}",
+            self.errors,
+            self.patterns,
+            self.corrected,
+            self.detected,
+            self.miscorrected,
+            self.undetected
+        )
+    }
+}
+
+// Most flipped bits the prompt enumerates: 4 bits of the 127,120 code are already 10 million
+// patterns, 6 bits are billions
+pub const MAX_COVERAGE_ERRORS: usize = 3;
+
+pub const COVERAGE_HEADER: &str = "errors   patterns  corrected   detected miscorrected undetected";
+
+/// The codes known by name, for the command line
+pub fn named_codes() -> Vec<(&'static str, HammingCode)> {
+    vec![
+        ("7,4", HammingCode::hamming_7_4()),
+        ("8,4", HammingCode::hamming_8_4()),
+        ("15,11", HammingCode::hamming_15_11()),
+        ("72,64", HammingCode::hamming_72_64()),
+        ("127,120", HammingCode::hamming_127_120()),
+    ]
+}
+
+/// Flips every combination of 1 to `max_errors` bits of the codeword of `data` and decodes it.
+///
+/// Hamming codes are linear, so the outcome of a pattern is the same for every data word,
+/// but a broken codec may not be: run it on a few words when changing the decoder.
+pub fn error_coverage(
+    code: &HammingCode,
+    data: &[Bit],
+    max_errors: usize,
+) -> Result<Vec<CoverageRow>, HammingError> {
+    let codeword = code.encode(data)?;
+    let mut rows = Vec::with_capacity(max_errors);
+
+    for errors in 1..=max_errors.min(codeword.len()) {
+        let mut row = CoverageRow {
+            errors,
+            ..CoverageRow::default()
+        };
+        // Positions of the flipped bits, in increasing order
+        let mut pattern: Vec<usize> = (0..errors).collect();
+        loop {
+            let mut damaged = codeword.clone();
+            for position in &pattern {
+                damaged[*position] = !damaged[*position];
+            }
+            row.patterns += 1;
+            match code.decode(&damaged)? {
+                HammingDecodeResult::DoubleError => row.detected += 1,
+                HammingDecodeResult::NoError { decoded_bits } if decoded_bits != data => {
+                    row.undetected += 1
+                }
+                HammingDecodeResult::OneError { decoded_bits, .. } if decoded_bits != data => {
+                    row.miscorrected += 1
+                }
+                HammingDecodeResult::NoError { .. } | HammingDecodeResult::OneError { .. } => {
+                    row.corrected += 1
+                }
+            }
+
+            if !next_combination(&mut pattern, codeword.len()) {
+                break;
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+// Moves to the next combination of positions in lexicographic order
+fn next_combination(pattern: &mut [usize], length: usize) -> bool {
+    let count = pattern.len();
+    let Some(index) = (0..count).rev().find(|i| pattern[*i] < length - count + i) else {
+        return false;
+    };
+    pattern[index] += 1;
+    for i in index + 1..count {
+        pattern[i] = pattern[i - 1] + 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coverage::*;
+
+    fn data(code: &HammingCode) -> Vec<Bit> {
+        (0..code.data_bits()).map(|i| i % 3 == 0).collect()
+    }
+
+    fn binomial(n: u64, k: u64) -> u64 {
+        (1..=k).fold(1, |result, i| result * (n - k + i) / i)
+    }
+
+    #[test]
+    fn next_combination_test() {
+        let mut pattern = vec![0, 1];
+        let mut all = vec![pattern.clone()];
+        while next_combination(&mut pattern, 4) {
+            all.push(pattern.clone());
+        }
+        assert_eq!(all, vec![[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]]);
+    }
+
+    #[test]
+    fn sec_coverage_test() {
+        let code = HammingCode::hamming_15_11();
+        let rows = error_coverage(&code, &data(&code), 3).unwrap();
+        assert_eq!(rows.len(), 3);
+        for row in &rows {
+            assert_eq!(row.patterns, binomial(15, row.errors as u64));
+        }
+        assert_eq!(rows[0].corrected, 15);
+        // A perfect code takes every double error for a single one
+        assert_eq!(rows[1].miscorrected, 105);
+        // A triple error is a codeword, or one bit away from another codeword
+        assert_eq!(rows[2].undetected + rows[2].miscorrected, 455);
+        assert_eq!(rows[2].undetected, 35);
+    }
+
+    #[test]
+    fn secded_coverage_test() {
+        for code in [HammingCode::hamming_8_4(), HammingCode::hamming_72_64()] {
+            let rows = error_coverage(&code, &data(&code), 3).unwrap();
+            let n = code.codeword_len() as u64;
+            assert_eq!(rows[0].corrected, n);
+            assert_eq!(rows[1].detected, binomial(n, 2));
+            // An odd number of errors looks like a single one, unless the syndrome
+            // points beyond the positions of a shortened code
+            assert_eq!(rows[2].corrected, 0);
+            assert_eq!(rows[2].detected + rows[2].miscorrected, binomial(n, 3));
+        }
+        let code = HammingCode::hamming_8_4();
+        let rows = error_coverage(&code, &data(&code), 3).unwrap();
+        assert_eq!(rows[2].miscorrected, 56);
+    }
+
+    #[test]
+    fn shortened_code_test() {
+        // The array code: syndromes beyond the positions of a shortened code are detected
+        let code = HammingCode::with_data_bits(8, false).unwrap();
+        let rows = error_coverage(&code, &data(&code), 2).unwrap();
+        assert_eq!(rows[0].corrected, 12);
+        assert!(rows[1].detected > 0);
+        assert_eq!(rows[1].detected + rows[1].miscorrected, 66);
+
+        assert!(error_coverage(&code, &[true], 1).is_err());
+    }
+}
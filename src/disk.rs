@@ -0,0 +1,124 @@
+use crate::hamming_encoding::Bit;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+// Stripes sharing one chunk: a write to a shared chunk copies this many bits
+pub(crate) const CHUNK_STRIPES: usize = 64;
+
+/// The bits of one disk, one per stripe.
+///
+/// Bits are kept in chunks of `CHUNK_STRIPES` behind `Arc`s, so a clone of the disk only
+/// copies pointers. A chunk shared with a clone is copied the first time it is changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Disk {
+    chunks: Vec<Arc<Vec<Bit>>>,
+    len: usize,
+}
+
+impl Disk {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn resize(&mut self, len: usize, value: Bit) {
+        self.chunks.truncate(len.div_ceil(CHUNK_STRIPES));
+        // The last chunk may be partial
+        let full_chunks = self.chunks.len().saturating_sub(1);
+        if let Some(last) = self.chunks.last_mut() {
+            let chunk_len = (len - full_chunks * CHUNK_STRIPES).min(CHUNK_STRIPES);
+            if last.len() != chunk_len {
+                Arc::make_mut(last).resize(chunk_len, value);
+            }
+        }
+        let mut stored = self.chunks.len() * CHUNK_STRIPES;
+        while stored < len {
+            let chunk_len = (len - stored).min(CHUNK_STRIPES);
+            self.chunks.push(Arc::new(vec![value; chunk_len]));
+            stored += chunk_len;
+        }
+        self.len = len;
+    }
+
+    pub(crate) fn fill(&mut self, value: Bit) {
+        for chunk in &mut self.chunks {
+            Arc::make_mut(chunk).fill(value);
+        }
+    }
+
+    pub(crate) fn invert(&mut self) {
+        for chunk in &mut self.chunks {
+            for bit in Arc::make_mut(chunk).iter_mut() {
+                *bit = !*bit;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Bit> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    // Chunks of the disk which are stored once for both disks
+    #[cfg(test)]
+    pub(crate) fn shared_chunks(&self, other: &Disk) -> usize {
+        self.chunks
+            .iter()
+            .zip(&other.chunks)
+            .filter(|(chunk, other)| Arc::ptr_eq(chunk, other))
+            .count()
+    }
+}
+
+impl Index<usize> for Disk {
+    type Output = Bit;
+
+    fn index(&self, position: usize) -> &Bit {
+        &self.chunks[position / CHUNK_STRIPES][position % CHUNK_STRIPES]
+    }
+}
+
+impl IndexMut<usize> for Disk {
+    fn index_mut(&mut self, position: usize) -> &mut Bit {
+        &mut Arc::make_mut(&mut self.chunks[position / CHUNK_STRIPES])[position % CHUNK_STRIPES]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disk::*;
+
+    #[test]
+    fn resize_test() {
+        let mut disk = Disk::new();
+        disk.resize(100, true);
+        assert_eq!(disk.len(), 100);
+        assert_eq!(disk.iter().count(), 100);
+        disk[99] = false;
+        disk.resize(130, false);
+        assert!(!disk[99] && disk[98] && !disk[129]);
+        disk.resize(10, false);
+        assert_eq!(disk.iter().count(), 10);
+        assert!(disk[9]);
+    }
+
+    #[test]
+    fn copy_on_write_test() {
+        let mut disk = Disk::new();
+        disk.resize(3 * CHUNK_STRIPES, false);
+        let snapshot = disk.clone();
+        assert_eq!(disk.shared_chunks(&snapshot), 3);
+
+        disk[CHUNK_STRIPES + 1] = true;
+        assert_eq!(disk.shared_chunks(&snapshot), 2);
+        assert!(!snapshot[CHUNK_STRIPES + 1]);
+        assert!(disk[CHUNK_STRIPES + 1]);
+
+        disk.invert();
+        assert_eq!(disk.shared_chunks(&snapshot), 0);
+        assert!(snapshot.iter().all(|bit| !bit));
+    }
+}
@@ -0,0 +1,207 @@
+use raid_ii_emulation::coverage::named_codes;
+use raid_ii_emulation::tokenizer::escape_bytes;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::PathBuf;
+
+pub const COMMANDS: &[&str] = &[
+    "write", "read", "corrupt", "fail", "replace", "status", "spares", "reshape", "delete", "undo",
+    "redo", "snapshot", "restore", "desync", "sync", "bench", "coverage", "ls", "stat", "checksum",
+    "log", "logfile", "exit",
+];
+
+const HISTORY_FILE_NAME: &str = ".raid_ii_history";
+
+pub type ReplEditor = Editor<ReplHelper, FileHistory>;
+
+/// Completes command names, file names stored at the array and disk numbers.
+/// The REPL refreshes `file_names` and `disks_count` after every command.
+#[derive(Default)]
+pub struct ReplHelper {
+    pub file_names: Vec<String>,
+    pub disks_count: usize,
+}
+
+pub fn create_editor() -> rustyline::Result<ReplEditor> {
+    let mut editor = ReplEditor::new()?;
+    editor.set_helper(Some(ReplHelper::default()));
+    // There is no history on the first run
+    let _ = editor.load_history(&history_path());
+    Ok(editor)
+}
+
+pub fn save_history(editor: &mut ReplEditor) -> rustyline::Result<()> {
+    editor.save_history(&history_path())
+}
+
+// History lives in the home directory, or in the working directory if there is no home
+fn history_path() -> PathBuf {
+    match std::env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(HISTORY_FILE_NAME),
+        None => PathBuf::from(HISTORY_FILE_NAME),
+    }
+}
+
+impl ReplHelper {
+    fn candidates(&self, previous_words: &[String], prefix: &str) -> Vec<String> {
+        let argument_number = previous_words.len();
+        let variants: Vec<String> = match previous_words.first().map(String::as_str) {
+            None => COMMANDS.iter().map(|command| command.to_string()).collect(),
+            Some("read") | Some("stat") | Some("delete") if argument_number == 1 => {
+                self.file_names.clone()
+            }
+            Some("write") if argument_number == 2 => self.file_names.clone(),
+            Some("checksum") if argument_number == 1 => vec!["on".to_owned(), "off".to_owned()],
+            Some("coverage") if argument_number == 1 => std::iter::once("array")
+                .chain(named_codes().into_iter().map(|(name, _)| name))
+                .map(str::to_owned)
+                .collect(),
+            Some("corrupt") | Some("fail") | Some("replace") | Some("desync")
+                if argument_number == 1 =>
+            {
+                (1..=self.disks_count)
+                    .map(|disk_number| disk_number.to_string())
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        variants
+            .into_iter()
+            .filter(|variant| variant.starts_with(prefix))
+            .collect()
+    }
+}
+
+// Splits the line before the cursor into finished words and the start of the current one.
+// Quotes are tracked the same way as in the tokenizer, escapes are left as they are.
+fn split_before_cursor(line: &str) -> (Vec<String>, usize) {
+    let mut words = Vec::new();
+    let mut word_start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('"'), '\\') | (None, '\\') => escaped = true,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, c) if c.is_whitespace() => {
+                if word_start < index {
+                    words.push(line[word_start..index].to_owned());
+                }
+                word_start = index + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+
+    (words, word_start)
+}
+
+fn quote_if_needed(word: &str) -> String {
+    if !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_-.".contains(c))
+    {
+        word.to_owned()
+    } else {
+        format!("\"{}\"", escape_bytes(word.as_bytes()))
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (words, word_start) = split_before_cursor(&line[..pos]);
+        let prefix = line[word_start..pos].trim_start_matches(['"', '\'']);
+        let pairs = self
+            .candidates(&words, prefix)
+            .into_iter()
+            .map(|candidate| Pair {
+                replacement: quote_if_needed(&candidate),
+                display: candidate,
+            })
+            .collect();
+        Ok((word_start, pairs))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::*;
+
+    fn helper() -> ReplHelper {
+        ReplHelper {
+            file_names: vec!["notes".to_owned(), "my notes".to_owned(), "log".to_owned()],
+            disks_count: 13,
+        }
+    }
+
+    #[test]
+    fn split_before_cursor_test() {
+        assert_eq!(split_before_cursor("re"), (vec![], 0));
+        assert_eq!(
+            split_before_cursor("write \"a b\" no"),
+            (vec!["write".to_owned(), "\"a b\"".to_owned()], 12)
+        );
+        assert_eq!(
+            split_before_cursor("read 'my n"),
+            (vec!["read".to_owned()], 5)
+        );
+    }
+
+    #[test]
+    fn candidates_test() {
+        let helper = helper();
+        assert_eq!(
+            helper.candidates(&[], "c"),
+            vec!["corrupt", "coverage", "checksum"]
+        );
+        assert_eq!(
+            helper.candidates(&["coverage".to_owned()], "7"),
+            vec!["7,4", "72,64"]
+        );
+        assert_eq!(helper.candidates(&["read".to_owned()], "no"), vec!["notes"]);
+        assert_eq!(
+            helper.candidates(&["write".to_owned(), "data".to_owned()], "my"),
+            vec!["my notes"]
+        );
+        assert!(helper.candidates(&["write".to_owned()], "no").is_empty());
+        assert_eq!(
+            helper.candidates(&["corrupt".to_owned()], "1"),
+            vec!["1", "10", "11", "12", "13"]
+        );
+    }
+
+    #[test]
+    fn quote_if_needed_test() {
+        assert_eq!(quote_if_needed("notes"), "notes");
+        assert_eq!(quote_if_needed("my notes"), "\"my notes\"");
+    }
+}
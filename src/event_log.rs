@@ -0,0 +1,400 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    FileWritten {
+        name: String,
+        size: usize,
+    },
+    FileRead {
+        name: String,
+        corrected_bits: usize,
+    },
+    FileDeleted {
+        name: String,
+        size: usize,
+    },
+    // A bit restored by the Hamming code, disk numbers start from 1
+    Corrected {
+        disk_number: usize,
+        position: usize,
+    },
+    Uncorrectable {
+        position: usize,
+    },
+    // The file checksum does not match the decoded content
+    SilentCorruption {
+        name: String,
+    },
+    DiskCorrupted {
+        disk_number: usize,
+    },
+    BitFlipped {
+        disk_number: usize,
+        position: usize,
+    },
+    DiskFailed {
+        disk_number: usize,
+    },
+    DiskSuspect {
+        disk_number: usize,
+    },
+    DiskReplaced {
+        disk_number: usize,
+    },
+    // A hot spare takes the place of the disk, it is rebuilt next
+    SpareActivated {
+        disk_number: usize,
+    },
+    RebuildFinished {
+        disk_number: usize,
+        lost_positions: usize,
+    },
+    SnapshotTaken {
+        id: usize,
+    },
+    SnapshotRestored {
+        id: usize,
+    },
+    Undone {
+        operation: String,
+    },
+    Redone {
+        operation: String,
+    },
+    ReshapeStarted {
+        from_data_disks: usize,
+        to_data_disks: usize,
+    },
+    ReshapeFinished {
+        data_disks: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub sequence: u64,
+    // Milliseconds since the Unix epoch
+    pub timestamp: u128,
+    pub kind: EventKind,
+}
+
+/// Append-only journal of the array operations.
+/// Every event can also be written as a JSON line to a file on the host.
+#[derive(Default)]
+pub struct EventLog {
+    events: Vec<Event>,
+    sink: Option<BufWriter<File>>,
+    sink_error: Option<io::Error>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, kind: EventKind) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or(0);
+        let event = Event {
+            sequence: self.events.len() as u64,
+            timestamp,
+            kind,
+        };
+
+        if let Some(sink) = &mut self.sink {
+            let written = writeln!(sink, "{}", event.to_json()).and_then(|_| sink.flush());
+            if let Err(error) = written {
+                // The log in memory is still complete, the sink is not used anymore
+                self.sink = None;
+                self.sink_error = Some(error);
+            }
+        }
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Appends all following events to the file as JSON lines
+    pub fn set_sink(&mut self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.sink = Some(BufWriter::new(file));
+        self.sink_error = None;
+        Ok(())
+    }
+
+    /// Returns the error which made the log stop writing to the sink
+    pub fn take_sink_error(&mut self) -> Option<io::Error> {
+        self.sink_error.take()
+    }
+}
+
+impl EventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EventKind::FileWritten { .. } => "file_written",
+            EventKind::FileRead { .. } => "file_read",
+            EventKind::FileDeleted { .. } => "file_deleted",
+            EventKind::Corrected { .. } => "corrected",
+            EventKind::Uncorrectable { .. } => "uncorrectable",
+            EventKind::SilentCorruption { .. } => "silent_corruption",
+            EventKind::DiskCorrupted { .. } => "disk_corrupted",
+            EventKind::BitFlipped { .. } => "bit_flipped",
+            EventKind::DiskFailed { .. } => "disk_failed",
+            EventKind::DiskSuspect { .. } => "disk_suspect",
+            EventKind::DiskReplaced { .. } => "disk_replaced",
+            EventKind::SpareActivated { .. } => "spare_activated",
+            EventKind::RebuildFinished { .. } => "rebuild_finished",
+            EventKind::SnapshotTaken { .. } => "snapshot_taken",
+            EventKind::SnapshotRestored { .. } => "snapshot_restored",
+            EventKind::Undone { .. } => "undone",
+            EventKind::Redone { .. } => "redone",
+            EventKind::ReshapeStarted { .. } => "reshape_started",
+            EventKind::ReshapeFinished { .. } => "reshape_finished",
+        }
+    }
+}
+
+impl Event {
+    pub fn to_json(&self) -> String {
+        let fields = match &self.kind {
+            EventKind::FileWritten { name, size } | EventKind::FileDeleted { name, size } => {
+                format!(",\"name\":{},\"size\":{}", json_string(name), size)
+            }
+            EventKind::FileRead {
+                name,
+                corrected_bits,
+            } => format!(
+                ",\"name\":{},\"corrected_bits\":{}",
+                json_string(name),
+                corrected_bits
+            ),
+            EventKind::Corrected {
+                disk_number,
+                position,
+            }
+            | EventKind::BitFlipped {
+                disk_number,
+                position,
+            } => format!(",\"disk\":{},\"position\":{}", disk_number, position),
+            EventKind::Uncorrectable { position } => format!(",\"position\":{}", position),
+            EventKind::SilentCorruption { name } => format!(",\"name\":{}", json_string(name)),
+            EventKind::DiskCorrupted { disk_number }
+            | EventKind::DiskFailed { disk_number }
+            | EventKind::DiskSuspect { disk_number }
+            | EventKind::DiskReplaced { disk_number }
+            | EventKind::SpareActivated { disk_number } => format!(",\"disk\":{}", disk_number),
+            EventKind::RebuildFinished {
+                disk_number,
+                lost_positions,
+            } => format!(
+                ",\"disk\":{},\"lost_positions\":{}",
+                disk_number, lost_positions
+            ),
+            EventKind::SnapshotTaken { id } | EventKind::SnapshotRestored { id } => {
+                format!(",\"snapshot\":{}", id)
+            }
+            EventKind::Undone { operation } | EventKind::Redone { operation } => {
+                format!(",\"operation\":{}", json_string(operation))
+            }
+            EventKind::ReshapeStarted {
+                from_data_disks,
+                to_data_disks,
+            } => format!(
+                ",\"from_data_disks\":{},\"to_data_disks\":{}",
+                from_data_disks, to_data_disks
+            ),
+            EventKind::ReshapeFinished { data_disks } => {
+                format!(",\"data_disks\":{}", data_disks)
+            }
+        };
+
+        format!(
+            "{{\"sequence\":{},\"timestamp\":{},\"event\":\"{}\"{}}}",
+            self.sequence,
+            self.timestamp,
+            self.kind.name(),
+            fields
+        )
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::FileWritten { name, size } => {
+                write!(f, "file {:?} written, {} bytes", name, size)
+            }
+            EventKind::FileRead {
+                name,
+                corrected_bits,
+            } => write!(f, "file {:?} read, {} bits corrected", name, corrected_bits),
+            EventKind::FileDeleted { name, size } => {
+                write!(f, "file {:?} deleted, {} bytes", name, size)
+            }
+            EventKind::Corrected {
+                disk_number,
+                position,
+            } => write!(f, "bit corrected on disk {} at {}", disk_number, position),
+            EventKind::Uncorrectable { position } => {
+                write!(f, "uncorrectable stripe at {}", position)
+            }
+            EventKind::SilentCorruption { name } => {
+                write!(f, "file {:?} does not match its checksum", name)
+            }
+            EventKind::DiskCorrupted { disk_number } => write!(f, "disk {} corrupted", disk_number),
+            EventKind::BitFlipped {
+                disk_number,
+                position,
+            } => write!(f, "bit flipped on disk {} at {}", disk_number, position),
+            EventKind::DiskFailed { disk_number } => write!(f, "disk {} failed", disk_number),
+            EventKind::DiskSuspect { disk_number } => {
+                write!(f, "disk {} marked as suspect", disk_number)
+            }
+            EventKind::DiskReplaced { disk_number } => write!(f, "disk {} replaced", disk_number),
+            EventKind::SpareActivated { disk_number } => {
+                write!(f, "hot spare takes the place of disk {}", disk_number)
+            }
+            EventKind::RebuildFinished {
+                disk_number,
+                lost_positions,
+            } => write!(
+                f,
+                "disk {} rebuilt, {} positions lost",
+                disk_number, lost_positions
+            ),
+            EventKind::SnapshotTaken { id } => write!(f, "snapshot {} taken", id),
+            EventKind::SnapshotRestored { id } => write!(f, "snapshot {} restored", id),
+            EventKind::Undone { operation } => write!(f, "{:?} undone", operation),
+            EventKind::Redone { operation } => write!(f, "{:?} redone", operation),
+            EventKind::ReshapeStarted {
+                from_data_disks,
+                to_data_disks,
+            } => write!(
+                f,
+                "reshape from {} to {} data disks started",
+                from_data_disks, to_data_disks
+            ),
+            EventKind::ReshapeFinished { data_disks } => {
+                write!(f, "reshaped to {} data disks", data_disks)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    // Time of the day in UTC, there is no need for the date in a REPL session
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let milliseconds = self.timestamp % (24 * 60 * 60 * 1000);
+        write!(
+            f,
+            "#{} {:02}:{:02}:{:02}.{:03} {}",
+            self.sequence,
+            milliseconds / 3_600_000,
+            milliseconds / 60_000 % 60,
+            milliseconds / 1000 % 60,
+            milliseconds % 1000,
+            self.kind
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event_log::*;
+
+    #[test]
+    fn record_test() {
+        let mut log = EventLog::new();
+        log.record(EventKind::DiskFailed { disk_number: 3 });
+        log.record(EventKind::Corrected {
+            disk_number: 5,
+            position: 7,
+        });
+        let events = log.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].sequence, 1);
+        assert!(events[0].timestamp <= events[1].timestamp);
+        assert_eq!(events[0].kind, EventKind::DiskFailed { disk_number: 3 });
+    }
+
+    #[test]
+    fn to_json_test() {
+        let event = Event {
+            sequence: 4,
+            timestamp: 1700000000123,
+            kind: EventKind::FileWritten {
+                name: "my \"notes\"\n".to_owned(),
+                size: 12,
+            },
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"sequence":4,"timestamp":1700000000123,"event":"file_written","name":"my \"notes\"\n","size":12}"#
+        );
+    }
+
+    #[test]
+    fn display_test() {
+        let event = Event {
+            sequence: 2,
+            timestamp: 3_723_004,
+            kind: EventKind::Uncorrectable { position: 9 },
+        };
+        assert_eq!(
+            event.to_string(),
+            "#2 01:02:03.004 uncorrectable stripe at 9"
+        );
+    }
+
+    #[test]
+    fn sink_test() {
+        let path =
+            std::env::temp_dir().join(format!("raid_ii_events_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut log = EventLog::new();
+        log.record(EventKind::DiskCorrupted { disk_number: 1 });
+        log.set_sink(&path).unwrap();
+        log.record(EventKind::DiskReplaced { disk_number: 2 });
+        log.record(EventKind::RebuildFinished {
+            disk_number: 2,
+            lost_positions: 0,
+        });
+        assert!(log.take_sink_error().is_none());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"sequence\":1,"));
+        assert!(lines[0].ends_with("\"event\":\"disk_replaced\",\"disk\":2}"));
+        assert!(lines[1].ends_with("\"disk\":2,\"lost_positions\":0}"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+use crate::checksum::Crc32;
+use crate::raid::RaidII;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Handle of a file stored at the array, created by `RaidII::open` or `RaidII::create`.
+///
+/// The file checksum can only be verified when the whole file is read from the start to the
+/// end without seeking, a mismatch is reported as an `InvalidData` error by the read which
+/// reaches the end of the file. Single invalid bits found by the reads are restored once the
+/// checksum matches, a miscorrection is never written back. Files without a checksum are
+/// restored right away, as `read_file` does.
+/// Writes overwrite the file in place, only the last file of the array can grow. The
+/// checksum is recomputed by `flush`, or when the handle is dropped.
+pub struct RaidFile<'a> {
+    raid: &'a mut RaidII,
+    index: usize,
+    position: u64,
+    // Checksum of the bytes read so far, while the file is read in order from the start
+    read_crc: Option<Crc32>,
+    // Corrections found by the reads so far, waiting for the checksum
+    pending_corrections: Vec<(usize, usize)>,
+    modified: bool,
+}
+
+impl<'a> RaidFile<'a> {
+    pub(crate) fn new(raid: &'a mut RaidII, index: usize) -> Self {
+        RaidFile {
+            raid,
+            index,
+            position: 0,
+            read_crc: Some(Crc32::new()),
+            pending_corrections: Vec::new(),
+            modified: false,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.raid.file_at(self.index).size() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Read for RaidFile<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let file = self.raid.file_at(self.index);
+        let (start_pos, size, checksum) = (file.start_pos(), file.size() as u64, file.checksum());
+        if self.position >= size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let count = buf.len().min((size - self.position) as usize);
+        let (bytes, invalid_position, corrections) = self
+            .raid
+            .read_range(start_pos + self.position as usize, count);
+        if checksum.is_none() {
+            self.raid.restore_bits(&corrections);
+        } else if self.read_crc.is_some() {
+            self.pending_corrections.extend(corrections);
+        }
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        match invalid_position {
+            // Return what is read, the next call reports the error
+            Some(_) if !bytes.is_empty() => self.advance(&buf[..bytes.len()], size, checksum),
+            Some(position) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("stripe {} can not be decoded", position),
+            )),
+            None => self.advance(&buf[..count], size, checksum),
+        }
+    }
+}
+
+impl RaidFile<'_> {
+    fn advance(&mut self, read: &[u8], size: u64, checksum: Option<u32>) -> io::Result<usize> {
+        self.position += read.len() as u64;
+        if let Some(crc) = &mut self.read_crc {
+            crc.update(read);
+            if self.position == size {
+                let corrections = std::mem::take(&mut self.pending_corrections);
+                if checksum.is_some_and(|checksum| checksum != crc.finish()) {
+                    self.read_crc = None;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the file does not match its checksum",
+                    ));
+                }
+                self.raid.restore_bits(&corrections);
+            }
+        }
+        Ok(read.len())
+    }
+}
+
+impl Write for RaidFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (written, byte) in buf.iter().enumerate() {
+            if !self
+                .raid
+                .write_file_byte(self.index, self.position as usize, *byte)
+            {
+                if written > 0 {
+                    return Ok(written);
+                }
+                let kind = if self.position > self.len() {
+                    io::ErrorKind::InvalidInput
+                } else {
+                    io::ErrorKind::StorageFull
+                };
+                return Err(io::Error::new(
+                    kind,
+                    "only the last file can grow, and only from its end while there is free space",
+                ));
+            }
+            self.position += 1;
+            self.modified = true;
+            self.read_crc = None;
+            self.pending_corrections.clear();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.modified {
+            self.raid.finish_file_write(self.index)?;
+            self.modified = false;
+        }
+        Ok(())
+    }
+}
+
+impl Seek for RaidFile<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let Some(position) = position else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the file",
+            ));
+        };
+
+        if position != self.position {
+            self.read_crc = (position == 0).then(Crc32::new);
+            // The checksum can not confirm them anymore, a new read from the start finds them again
+            self.pending_corrections.clear();
+        }
+        self.position = position;
+        Ok(position)
+    }
+}
+
+impl Drop for RaidFile<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raid::*;
+    use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+    #[test]
+    fn read_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let bytes = b"first line\nsecond line\n".to_vec();
+        raid_ii.write_file(&bytes, FileType::Text, "lines");
+        raid_ii.corrupt_disk(6);
+
+        let file = raid_ii.open("lines").unwrap();
+        assert_eq!(file.len(), bytes.len() as u64);
+        let lines: Vec<String> = BufReader::new(file).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, vec!["first line", "second line"]);
+        let health = raid_ii.file_health("lines").unwrap();
+        assert_eq!(health.clean, bytes.len());
+        assert!(raid_ii.open("missing").is_none());
+    }
+
+    #[test]
+    fn seek_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello, Rust!", FileType::Text, "greeting");
+        let mut file = raid_ii.open("greeting").unwrap();
+
+        let mut word = [0; 4];
+        assert_eq!(file.seek(SeekFrom::Start(7)).unwrap(), 7);
+        file.read_exact(&mut word).unwrap();
+        assert_eq!(&word, b"Rust");
+        assert_eq!(file.seek(SeekFrom::End(-12)).unwrap(), 0);
+        file.seek(SeekFrom::Current(2)).unwrap();
+        file.read_exact(&mut word).unwrap();
+        assert_eq!(&word, b"llo,");
+        assert!(file.seek(SeekFrom::Current(-10)).is_err());
+        file.seek(SeekFrom::End(5)).unwrap();
+        assert_eq!(file.read(&mut word).unwrap(), 0);
+    }
+
+    #[test]
+    fn write_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        {
+            let mut file = raid_ii.create("copy", FileType::Text).unwrap();
+            let mut source: &[u8] = b"Hello, Rust!";
+            assert_eq!(io::copy(&mut source, &mut file).unwrap(), 12);
+            file.seek(SeekFrom::Start(7)).unwrap();
+            file.write_all(b"RAID").unwrap();
+        }
+
+        let file = raid_ii.files().find(|file| file.name() == "copy").unwrap();
+        assert_eq!(file.size(), 12);
+        assert_eq!(
+            file.checksum(),
+            Some(crate::checksum::crc32(b"Hello, RAID!"))
+        );
+        match raid_ii.read_file("copy") {
+            FileReadResult::Success(_, bytes) => assert_eq!(bytes, b"Hello, RAID!"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn write_limits_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"first", FileType::Text, "first");
+        raid_ii.write_file(b"second", FileType::Text, "second");
+
+        let mut file = raid_ii.open("first").unwrap();
+        file.write_all(b"FIRST").unwrap();
+        let error = file.write(b"!").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+        drop(file);
+
+        let mut file = raid_ii.open("second").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b" file").unwrap();
+        file.seek(SeekFrom::End(3)).unwrap();
+        let error = file.write(b"!").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        drop(file);
+
+        let mut content = String::new();
+        raid_ii
+            .open("second")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "second file");
+    }
+
+    #[test]
+    fn create_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"taken", FileType::Text, "taken");
+        assert!(matches!(
+            raid_ii.create("taken", FileType::Text),
+            Err(CreateError::NameTaken)
+        ));
+
+        // Both files start at the same position, only the second one is last
+        drop(raid_ii.create("first", FileType::Text).unwrap());
+        drop(raid_ii.create("second", FileType::Text).unwrap());
+        let error = raid_ii.open("first").unwrap().write(b"1").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+        raid_ii.open("second").unwrap().write_all(b"2").unwrap();
+        match raid_ii.read_file("second") {
+            FileReadResult::Success(_, bytes) => assert_eq!(bytes, b"2"),
+            _ => unreachable!(),
+        }
+        match raid_ii.read_file("first") {
+            FileReadResult::Success(_, bytes) => assert!(bytes.is_empty()),
+            _ => unreachable!(),
+        }
+
+        raid_ii.write_file(&vec![0; raid_ii.free_space() - 1], FileType::Text, "rest");
+        assert!(matches!(
+            raid_ii.create("full", FileType::Text),
+            Err(CreateError::NotEnoughSpace)
+        ));
+
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.fail_disk(3);
+        raid_ii.replace_disk(3);
+        assert!(matches!(
+            raid_ii.create("rebuilding", FileType::Text),
+            Err(CreateError::Busy)
+        ));
+    }
+
+    #[test]
+    fn read_errors_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello, Rust!", FileType::Text, "greeting");
+        raid_ii.corrupt_disk(1);
+        raid_ii.corrupt_disk(2);
+        let mut bytes = Vec::new();
+        let error = raid_ii
+            .open("greeting")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Three errors are "corrected" wrong, the checksum notices it at the end of the file
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello, Rust!", FileType::Text, "greeting");
+        raid_ii.corrupt_disk(2);
+        raid_ii.corrupt_disk(3);
+        raid_ii.corrupt_disk(4);
+        let mut bytes = Vec::new();
+        let error = raid_ii
+            .open("greeting")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // The wrong corrections are not written back
+        assert!(raid_ii
+            .status()
+            .disks
+            .iter()
+            .all(|disk| disk.corrected_errors == 0));
+    }
+}
@@ -1,5 +1,8 @@
+use std::fmt;
+
 pub type Bit = bool;
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum HammingDecodeResult {
     NoError {
         decoded_bits: Vec<Bit>,
@@ -8,10 +11,244 @@ pub enum HammingDecodeResult {
         position: usize, // Where the error occurred
         decoded_bits: Vec<Bit>,
     },
-    DoubleError,
+    DoubleError, // Two errors, or more errors which did not look like a single one
+}
+
+/// Parameters of a Hamming code.
+///
+/// A codeword has `data_bits + parity_bits` bits in the classic Hamming order, where the
+/// position `2^i` (counting from 1) holds the parity bit `i`. An extended code (SECDED)
+/// prepends one more bit with the parity of the whole block, so it also detects two errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HammingCode {
+    data_bits: usize,
+    parity_bits: usize,
+    extended: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HammingError {
+    InvalidParameters {
+        data_bits: usize,
+        parity_bits: usize,
+    },
+    WrongDataLength {
+        expected: usize,
+        actual: usize,
+    },
+    WrongCodewordLength {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for HammingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HammingError::InvalidParameters {
+                data_bits,
+                parity_bits,
+            } => write!(
+                f,
+                "{} parity bits can not protect {} data bits",
+                parity_bits, data_bits
+            ),
+            HammingError::WrongDataLength { expected, actual } => {
+                write!(f, "expected {} data bits, got {}", expected, actual)
+            }
+            HammingError::WrongCodewordLength { expected, actual } => {
+                write!(
+                    f,
+                    "expected a codeword of {} bits, got {}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+impl HammingCode {
+    pub fn new(data_bits: usize, parity_bits: usize, extended: bool) -> Result<Self, HammingError> {
+        // Every position of the codeword must have its own non-zero syndrome
+        let enough_syndromes =
+            parity_bits < usize::BITS as usize && (1usize << parity_bits) > data_bits + parity_bits;
+        if data_bits == 0 || parity_bits < 2 || !enough_syndromes {
+            return Err(HammingError::InvalidParameters {
+                data_bits,
+                parity_bits,
+            });
+        }
+
+        Ok(HammingCode {
+            data_bits,
+            parity_bits,
+            extended,
+        })
+    }
+
+    /// The code with the least number of parity bits for the given number of data bits
+    pub fn with_data_bits(data_bits: usize, extended: bool) -> Result<Self, HammingError> {
+        let mut parity_bits = 2;
+        while (1usize << parity_bits) <= data_bits + parity_bits {
+            parity_bits += 1;
+        }
+        Self::new(data_bits, parity_bits, extended)
+    }
+
+    pub fn hamming_7_4() -> Self {
+        HammingCode {
+            data_bits: 4,
+            parity_bits: 3,
+            extended: false,
+        }
+    }
+
+    pub fn hamming_8_4() -> Self {
+        HammingCode {
+            data_bits: 4,
+            parity_bits: 3,
+            extended: true,
+        }
+    }
+
+    pub fn hamming_15_11() -> Self {
+        HammingCode {
+            data_bits: 11,
+            parity_bits: 4,
+            extended: false,
+        }
+    }
+
+    pub fn hamming_72_64() -> Self {
+        HammingCode {
+            data_bits: 64,
+            parity_bits: 7,
+            extended: true,
+        }
+    }
+
+    pub fn hamming_127_120() -> Self {
+        HammingCode {
+            data_bits: 120,
+            parity_bits: 7,
+            extended: false,
+        }
+    }
+
+    pub fn data_bits(&self) -> usize {
+        self.data_bits
+    }
+
+    pub fn parity_bits(&self) -> usize {
+        self.parity_bits
+    }
+
+    pub fn extended(&self) -> bool {
+        self.extended
+    }
+
+    pub fn codeword_len(&self) -> usize {
+        self.data_bits + self.parity_bits + usize::from(self.extended)
+    }
+
+    // Index of the Hamming position 1 in the codeword
+    fn offset(&self) -> usize {
+        usize::from(self.extended)
+    }
+
+    pub fn encode(&self, data: &[Bit]) -> Result<Vec<Bit>, HammingError> {
+        if data.len() != self.data_bits {
+            return Err(HammingError::WrongDataLength {
+                expected: self.data_bits,
+                actual: data.len(),
+            });
+        }
+
+        let offset = self.offset();
+        let mut codeword = vec![false; self.codeword_len()];
+        let mut data_bits = data.iter();
+        for position in 1..=self.data_bits + self.parity_bits {
+            if !position.is_power_of_two() {
+                codeword[offset + position - 1] = *data_bits.next().unwrap_or(&false);
+            }
+        }
+
+        for i in 0..self.parity_bits {
+            let mask = 1 << i;
+            codeword[offset + mask - 1] = self.parity(&codeword, mask);
+        }
+
+        if self.extended {
+            codeword[0] = codeword.iter().fold(false, |sum, bit| sum ^ *bit);
+        }
+        Ok(codeword)
+    }
+
+    pub fn decode(&self, codeword: &[Bit]) -> Result<HammingDecodeResult, HammingError> {
+        if codeword.len() != self.codeword_len() {
+            return Err(HammingError::WrongCodewordLength {
+                expected: self.codeword_len(),
+                actual: codeword.len(),
+            });
+        }
+
+        let offset = self.offset();
+        let positions = self.data_bits + self.parity_bits;
+        let syndrome: usize = (0..self.parity_bits)
+            .map(|i| 1 << i)
+            .filter(|mask| self.parity(codeword, *mask))
+            .sum();
+        let block_parity = codeword.iter().fold(false, |sum, bit| sum ^ *bit);
+
+        if syndrome == 0 && !(self.extended && block_parity) {
+            return Ok(HammingDecodeResult::NoError {
+                decoded_bits: self.extract_data(codeword),
+            });
+        }
+
+        // Index of the single invalid bit. With an even block parity there are two errors,
+        // a syndrome greater than the number of positions happens in shortened codes.
+        let invalid_bit = if self.extended && !block_parity {
+            None
+        } else if syndrome == 0 {
+            Some(0)
+        } else if syndrome <= positions {
+            Some(offset + syndrome - 1)
+        } else {
+            None
+        };
+
+        Ok(match invalid_bit {
+            Some(position) => {
+                let mut corrected = codeword.to_vec();
+                corrected[position] = !corrected[position];
+                HammingDecodeResult::OneError {
+                    position,
+                    decoded_bits: self.extract_data(&corrected),
+                }
+            }
+            None => HammingDecodeResult::DoubleError,
+        })
+    }
+
+    // Parity of the Hamming positions covered by the mask
+    fn parity(&self, codeword: &[Bit], mask: usize) -> Bit {
+        let offset = self.offset();
+        (1..=self.data_bits + self.parity_bits)
+            .filter(|position| position & mask == mask)
+            .fold(false, |sum, position| sum ^ codeword[offset + position - 1])
+    }
+
+    fn extract_data(&self, codeword: &[Bit]) -> Vec<Bit> {
+        let offset = self.offset();
+        (1..=self.data_bits + self.parity_bits)
+            .filter(|position| !position.is_power_of_two())
+            .map(|position| codeword[offset + position - 1])
+            .collect()
+    }
 }
 
-pub fn encode(bits: &Vec<Bit>) -> Vec<Bit> {
+pub fn encode(bits: &[Bit]) -> Vec<Bit> {
     let mut extra_bits = 0;
     let data_len = bits.len();
 
@@ -45,9 +282,9 @@ pub fn encode(bits: &Vec<Bit>) -> Vec<Bit> {
         let mask = 1 << i;
         let mut parity_bit = false;
 
-        for j in 1..encoded_data.len() {
+        for (j, bit) in encoded_data.iter().enumerate().skip(1) {
             if j & mask == mask {
-                parity_bit ^= encoded_data[j];
+                parity_bit ^= *bit;
             }
         }
 
@@ -60,7 +297,7 @@ pub fn encode(bits: &Vec<Bit>) -> Vec<Bit> {
     encoded_data
 }
 
-pub fn decode(bits: &mut Vec<Bit>) -> HammingDecodeResult {
+pub fn decode(bits: &mut [Bit]) -> HammingDecodeResult {
     // Count parity_bits_number
     let mut parity_bits_number = 0;
     while 1 << parity_bits_number < bits.len() {
@@ -74,8 +311,8 @@ pub fn decode(bits: &mut Vec<Bit>) -> HammingDecodeResult {
         let mask = 1 << i;
         let mut parity_bits_sum = 0;
 
-        for j in 1..bits.len() {
-            if j & mask == mask && bits[j] {
+        for (j, bit) in bits.iter().enumerate().skip(1) {
+            if j & mask == mask && *bit {
                 parity_bits_sum += 1;
             }
         }
@@ -88,84 +325,71 @@ pub fn decode(bits: &mut Vec<Bit>) -> HammingDecodeResult {
     let decoded_bits = bits.len() - parity_bits_number;
 
     if !block_parity && wrong_parity_bits.is_empty() {
-        let decoded_bits = get_inner_data(&bits, decoded_bits);
+        let decoded_bits = get_inner_data(bits, decoded_bits);
         HammingDecodeResult::NoError { decoded_bits }
-    } else if block_parity {
+    } else if block_parity && wrong_parity_bits.iter().sum::<usize>() < bits.len() {
         // One mistake
-        let position = wrong_parity_bits.iter().sum();
-        bits[position] = !(bits[position] as Bit);
-        let decoded_bits = get_inner_data(&bits, decoded_bits);
+        let position: usize = wrong_parity_bits.iter().sum();
+        bits[position] = !bits[position];
+        let decoded_bits = get_inner_data(bits, decoded_bits);
         HammingDecodeResult::OneError {
             position,
             decoded_bits,
         }
     } else {
-        // Two mistakes
+        // Two mistakes, or an odd number of them pointing beyond the end of a codeword
+        // which is not full length
         HammingDecodeResult::DoubleError
     }
 }
 
-fn get_inner_data(encoded_bits: &Vec<Bit>, decoded_size: usize) -> Vec<Bit> {
+fn get_inner_data(encoded_bits: &[Bit], decoded_size: usize) -> Vec<Bit> {
     let mut inner_data = Vec::with_capacity(decoded_size);
     let mut power_counter = 0;
 
-    for i in 1..encoded_bits.len() {
+    for (i, bit) in encoded_bits.iter().enumerate().skip(1) {
         if 1 << power_counter == i {
             power_counter += 1;
         } else {
-            inner_data.push(encoded_bits[i]);
+            inner_data.push(*bit);
         }
     }
 
     inner_data
 }
 
-pub fn bit_vector_from_bytes(bytes: &Vec<u8>) -> Vec<Bit> {
-    let mut bits = vec![];
+pub fn bit_vector_from_bytes(bytes: &[u8]) -> Vec<Bit> {
+    let mut bits = Vec::with_capacity(bytes.len() * 8);
     for byte in bytes {
-        let mut tmp = *byte;
-        let mut byte_vector = Vec::with_capacity(8);
-        while tmp != 0 {
-            byte_vector.push(tmp % 2 != 0);
-            tmp >>= 1;
+        // Most significant bit first, zero bytes included
+        for shift in (0..8).rev() {
+            bits.push((byte >> shift) & 1 != 0);
         }
-
-        while byte_vector.len() % 8 != 0 {
-            byte_vector.push(false)
-        }
-
-        byte_vector.reverse();
-        bits.append(&mut byte_vector);
     }
 
     bits
 }
 
-pub fn bit_vector_to_bytes(bits: &Vec<Bit>) -> Vec<u8> {
-    let bytes_number = bits.len() / 8 + if bits.len() % 8 > 0 { 1 } else { 0 };
+pub fn bit_vector_to_bytes(bits: &[Bit]) -> Vec<u8> {
+    let bytes_number = bits.len().div_ceil(8);
     let mut byte_vector = vec![0; bytes_number];
-    let mut byte = 0;
-    for i in 0..bits.len() {
-        if bits[i] {
-            byte += 1 << 7 - (i % 8);
-        }
-
-        if (i + 1) % 8 == 0 {
-            byte_vector[i / 8] = byte;
-            byte = 0;
+    for (i, bit) in bits.iter().enumerate() {
+        // The last byte is padded with zeros when the length is not a multiple of 8
+        if *bit {
+            byte_vector[i / 8] |= 1 << (7 - (i % 8));
         }
     }
 
     byte_vector
 }
 
-pub fn bit_vector_to_string(bits: &Vec<Bit>) -> String {
+pub fn bit_vector_to_string(bits: &[Bit]) -> String {
     let mut str = "".to_owned();
-    for i in 0..bits.len() {
-        if bits[i] {
-            str.push_str("1");
+    for bit in bits {
+        if *bit {
+            str.push('1');
         } else {
-            str.push_str("0");
+            str.push('0');
         }
     }
     str
@@ -182,15 +406,17 @@ mod tests {
         let byte_data = vec![1; 4];
         let vector = bit_vector_from_bytes(&byte_data);
         assert_eq!(vector.len(), 4 * 8);
-        for i in 0..vector.len() {
-            if (i + 1) % 8 == 0 {
-                assert_eq!(vector[i], true)
-            } else {
-                assert_eq!(vector[i], false)
-            }
+        for (i, bit) in vector.iter().enumerate() {
+            assert_eq!(*bit, (i + 1) % 8 == 0)
         }
     }
 
+    #[test]
+    fn from_zero_byte_test() {
+        let vector = bit_vector_from_bytes(&[0, 0b10000000]);
+        assert_eq!(bit_vector_to_string(&vector), "0000000010000000");
+    }
+
     #[test]
     fn to_bytes_test() {
         let byte_data = vec![1; 10];
@@ -199,6 +425,13 @@ mod tests {
         assert_eq!(byte_data, new_bytes);
     }
 
+    #[test]
+    fn to_bytes_partial_test() {
+        let vector = vec![true, false, false, false, true, false];
+        assert_eq!(bit_vector_to_bytes(&vector), vec![0b10001000]);
+        assert_eq!(bit_vector_to_bytes(&[]), Vec::<u8>::new());
+    }
+
     #[test]
     fn to_string_test() {
         let byte_data = vec![0b00001110];
@@ -283,9 +516,164 @@ mod tests {
         let mut encoded_bits = encode(&vector);
         encoded_bits[0] = !encoded_bits[0];
         encoded_bits[1] = !encoded_bits[1];
-        match decode(&mut encoded_bits) {
-            HammingDecodeResult::DoubleError => assert!(true),
-            _ => assert!(false),
-        };
+        assert!(matches!(
+            decode(&mut encoded_bits),
+            HammingDecodeResult::DoubleError
+        ));
+    }
+
+    #[test]
+    fn decode_syndrome_out_of_range_test() {
+        // Three errors with the syndrome 7 in a codeword of 5 bits
+        let mut bits = vec![false, true, true, false, true];
+        assert_eq!(decode(&mut bits), HammingDecodeResult::DoubleError);
+        assert_eq!(bits, vec![false, true, true, false, true]);
+
+        assert_eq!(
+            decode(&mut []),
+            HammingDecodeResult::NoError {
+                decoded_bits: vec![]
+            }
+        );
+    }
+
+    // Tests for HammingCode
+
+    fn sample_data(length: usize) -> Vec<Bit> {
+        (0..length).map(|i| i % 3 == 0 || i % 7 == 1).collect()
+    }
+
+    fn standard_codes() -> Vec<(HammingCode, usize)> {
+        vec![
+            (HammingCode::hamming_7_4(), 7),
+            (HammingCode::hamming_8_4(), 8),
+            (HammingCode::hamming_15_11(), 15),
+            (HammingCode::hamming_72_64(), 72),
+            (HammingCode::hamming_127_120(), 127),
+        ]
+    }
+
+    #[test]
+    fn code_parameters_test() {
+        for (code, codeword_len) in standard_codes() {
+            assert_eq!(code.codeword_len(), codeword_len);
+            assert_eq!(
+                HammingCode::with_data_bits(code.data_bits(), code.extended()),
+                Ok(code)
+            );
+        }
+        assert_eq!(
+            HammingCode::new(5, 3, false),
+            Err(HammingError::InvalidParameters {
+                data_bits: 5,
+                parity_bits: 3
+            })
+        );
+        assert!(HammingCode::new(0, 3, true).is_err());
+        assert!(HammingCode::new(1, 1, false).is_err());
+        assert!(HammingCode::new(8, 200, false).is_err());
+    }
+
+    #[test]
+    fn code_matches_encode_test() {
+        let data = bit_vector_from_bytes(&[0b11110000]);
+        let code = HammingCode::with_data_bits(8, true).unwrap();
+        assert_eq!(code.codeword_len(), 13);
+        assert_eq!(code.encode(&data).unwrap(), encode(&data));
+    }
+
+    #[test]
+    fn code_round_trip_test() {
+        for (code, _) in standard_codes() {
+            let data = sample_data(code.data_bits());
+            let codeword = code.encode(&data).unwrap();
+            assert_eq!(
+                code.decode(&codeword),
+                Ok(HammingDecodeResult::NoError {
+                    decoded_bits: data.clone()
+                })
+            );
+
+            for position in 0..codeword.len() {
+                let mut damaged = codeword.clone();
+                damaged[position] = !damaged[position];
+                assert_eq!(
+                    code.decode(&damaged),
+                    Ok(HammingDecodeResult::OneError {
+                        position,
+                        decoded_bits: data.clone()
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn code_double_error_test() {
+        for (code, _) in standard_codes()
+            .into_iter()
+            .filter(|(code, _)| code.extended())
+        {
+            let codeword = code.encode(&sample_data(code.data_bits())).unwrap();
+            for first in 0..codeword.len() {
+                for second in first + 1..codeword.len() {
+                    let mut damaged = codeword.clone();
+                    damaged[first] = !damaged[first];
+                    damaged[second] = !damaged[second];
+                    assert_eq!(code.decode(&damaged), Ok(HammingDecodeResult::DoubleError));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn code_wrong_length_test() {
+        let code = HammingCode::hamming_8_4();
+        assert_eq!(
+            code.encode(&[true; 5]),
+            Err(HammingError::WrongDataLength {
+                expected: 4,
+                actual: 5
+            })
+        );
+        assert_eq!(
+            code.decode(&[true; 7]),
+            Err(HammingError::WrongCodewordLength {
+                expected: 8,
+                actual: 7
+            })
+        );
+        assert!(code.decode(&[]).is_err());
+    }
+
+    #[test]
+    fn sec_code_double_error_test() {
+        // (7,4) is a perfect code, every double error is taken for a single one
+        let code = HammingCode::hamming_7_4();
+        let data = vec![true, false, true, true];
+        let codeword = code.encode(&data).unwrap();
+        for first in 0..codeword.len() {
+            for second in first + 1..codeword.len() {
+                let mut damaged = codeword.clone();
+                damaged[first] = !damaged[first];
+                damaged[second] = !damaged[second];
+                match code.decode(&damaged) {
+                    Ok(HammingDecodeResult::OneError {
+                        position,
+                        decoded_bits,
+                    }) => {
+                        assert!(position != first && position != second);
+                        let mut corrected = damaged.clone();
+                        corrected[position] = !corrected[position];
+                        assert_ne!(corrected, codeword);
+                        assert_eq!(
+                            code.decode(&corrected).unwrap(),
+                            HammingDecodeResult::NoError { decoded_bits }
+                        );
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
     }
 }
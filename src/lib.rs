@@ -0,0 +1,30 @@
+//! Emulation of a RAID II disk array: data is interleaved bit by bit over the disks and
+//! protected by a Hamming code, so a damaged disk is corrected on the fly.
+//!
+//! The most used types are exported at the root of the crate, the modules hold the rest:
+//! the block device and network interfaces, timing and reliability models, the commands
+//! of the prompt.
+
+pub mod bench;
+pub mod block_device;
+pub mod checksum;
+pub mod commands;
+pub mod coverage;
+mod disk;
+pub mod event_log;
+pub mod file_handle;
+pub mod hamming_encoding;
+pub mod nbd;
+pub mod raid;
+pub mod reliability;
+pub mod shared;
+pub mod timing;
+pub mod tokenizer;
+pub mod workers;
+
+pub use block_device::{BlockDevice, BlockError};
+pub use hamming_encoding::{Bit, HammingCode, HammingDecodeResult, HammingError};
+pub use raid::{
+    ArrayState, ArrayStatus, CreateError, DeleteError, DiskState, File, FileReadResult, FileType,
+    FileWriteResult, GeometryError, RaidII, ReshapeProgress,
+};
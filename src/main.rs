@@ -1,132 +1,139 @@
-pub mod hamming_encoding;
-pub mod raid;
+mod editor;
 
-use raid::{FileType, RaidII};
-use std::io::{self, Write};
+use editor::ReplEditor;
+use raid_ii_emulation::commands::{self, Command, Data, Output, ParseError};
+use raid_ii_emulation::RaidII;
+use rustyline::error::ReadlineError;
+
+const PROMPT: &str = "Your command: ";
+const HEREDOC_PROMPT: &str = "> ";
 
 fn main() {
-    help();
-    let mut raid = RaidII::from_data_capacity(1024);
-    let mut user_input = String::new();
-    let mut stdout = io::stdout();
-    let stdin = io::stdin();
+    // `--sec` drops the whole block parity disk: single error correction only
+    let extended = !std::env::args().skip(1).any(|argument| argument == "--sec");
+    let mut raid = RaidII::new(1024, extended);
+    help(raid.total_disks());
+    let mut editor = match editor::create_editor() {
+        Ok(editor) => editor,
+        Err(error) => {
+            println!("Can not open the terminal: {}", error);
+            return;
+        }
+    };
 
     loop {
-        user_input.clear();
-        invite_to_enter_command();
-        let _ = stdout.flush();
-        match stdin.read_line(&mut user_input) {
-            Ok(_) => {
-                let tokens: Vec<&str> = user_input.split_whitespace().collect();
-                match tokens.first() {
-                    Some(string) => match *string {
-                        "write" => {
-                            if tokens.len() != 3 {
-                                say_error();
-                                continue;
-                            }
-
-                            let data = tokens[1].as_bytes().to_vec();
-                            let name = tokens[2].to_owned();
-                            let result = raid.write_file(&data, FileType::Text, &name);
-
-                            match result {
-                                raid::FileWriteResult::Success => println!(
-                                    "File {} with content '{}' has been written!",
-                                    name, tokens[1]
-                                ),
-                                raid::FileWriteResult::NotEnoughSpace => {
-                                    println!("Not enough space to store data!")
-                                }
-                            }
-                        }
-                        "read" => {
-                            if tokens.len() != 2 {
-                                say_error();
-                                continue;
-                            }
-
-                            let name = tokens[1].to_owned();
-                            let result = raid.read_file(&name);
-
-                            match result {
-                                raid::FileReadResult::NotFound => {
-                                    println!("File {} does not exist", name)
-                                }
-                                raid::FileReadResult::DisksCorrupted => println!(
-                                    "All data is corrupted. Failed to complete you request! Two disks are corrupted so there is no way to restore data :("
-                                ),
-                                raid::FileReadResult::Success(file_type, byte_data) => {
-                                    match file_type {
-                                        FileType::Text => match String::from_utf8(byte_data) {
-                                            Ok(content_string) => {
-                                                println!(
-                                                    "File: {}\nContent: {}",
-                                                    name, content_string
-                                                );
-                                            }
-                                            Err(_) => panic!(
-                                                "All data stored at RAID has UTF8 format. This can happen if more than 3 disks are damaged."
-                                            ),
-                                        },
-                                    }
-                                }
-                            }
-                        }
-                        "corrupt" => {
-                            if tokens.len() != 2 {
-                                say_error();
-                                continue;
-                            }
+        if let Some(helper) = editor.helper_mut() {
+            helper.file_names = raid.files().map(|file| file.name().to_owned()).collect();
+            helper.disks_count = raid.total_disks();
+        }
 
-                            match tokens[1].parse::<usize>() {
-                                Ok(disk_number) => {
-                                    if 0 < disk_number && disk_number <= 13 {
-                                        raid.corrupt_disk(disk_number);
-                                    } else {
-                                        say_error();
-                                        continue;
-                                    }
-                                }
-                                Err(_) => {
-                                    say_error();
-                                    continue;
-                                }
-                            }
-                        }
-                        "exit" => {
-                            break;
-                        }
-                        _ => {
-                            say_error();
-                            continue;
-                        }
-                    },
-                    None => {
+        match editor.readline(PROMPT) {
+            Err(ReadlineError::Eof) => break,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(error) => {
+                println!("Can not read the command: {}", error);
+                break;
+            }
+            Ok(user_input) => {
+                if let Some(error) = raid.take_event_sink_error() {
+                    println!("Events are not written to the log file anymore: {}", error);
+                }
+                if !user_input.trim().is_empty() {
+                    let _ = editor.add_history_entry(user_input.as_str());
+                }
+                let command = match commands::parse(&user_input) {
+                    Ok(command) => command,
+                    Err(ParseError::Tokenize(error)) => {
+                        println!("Can not parse the command: {}.", error);
+                        continue;
+                    }
+                    Err(ParseError::Empty) => {
                         println!("Can not read the command.");
                         continue;
                     }
+                    Err(_) => {
+                        println!("{}", Output::Invalid);
+                        continue;
+                    }
+                };
+                // The data of a heredoc is on the next lines
+                let command = match command {
+                    Command::Write {
+                        data: Data::Heredoc(tag),
+                        name,
+                    } => match read_heredoc(&mut editor, &tag) {
+                        Some(data) => Command::Write {
+                            data: Data::Inline(data),
+                            name,
+                        },
+                        None => {
+                            println!("Input ended before the '{}' line.", tag);
+                            continue;
+                        }
+                    },
+                    command => command,
+                };
+                match commands::execute(&mut raid, command) {
+                    Output::Exit => break,
+                    Output::Text(text) if text.is_empty() => {}
+                    output => println!("{}", output),
                 }
             }
-            Err(_) => (),
         }
     }
+
+    if let Err(error) = editor::save_history(&mut editor) {
+        println!("Can not save the command history: {}", error);
+    }
 }
 
-fn help() {
+fn help(total_disks: usize) {
     println!(
-        "This is a simulation of RAID II operation. Available commands:
-        - write str_data file_name
+        "This is a simulation of RAID II operation with {} disks. Available commands:
+        - write data file_name
+          (quote data with \"...\" or '...', escapes: \\n \\t \\\" \\xNN)
+        - write <<TAG file_name
+          (data is read from the following lines until a line equal to TAG)
         - read file_name
-        - corrupt disk_number(from 1 to 13)
-        - exit"
+        - delete file_name (the files written after it move down)
+        - corrupt disk_number(from 1 to {})
+        - fail disk_number
+        - replace disk_number (rebuilds a failed or suspect disk from the others)
+        - status
+        - spares count (hot spares replace failed and suspect disks, rebuilt while you go on)
+        - reshape data_disks [bytes] (re-encode the stripes for another number of data disks,
+          a multiple of 8; with bytes only that much is done, repeat the command to go on)
+        - snapshot (save the disks and the files)
+        - restore snapshot_id (go back to the snapshot)
+        - undo, redo (write, delete, corrupt, fail and replace, one command at a time)
+        - desync disk_number [rotation_fraction] (turn the disk spindle out of sync, 0.5 by default)
+        - sync (synchronize all spindles again)
+        - coverage [array|7,4|8,4|15,11|72,64|127,120] [max_errors]
+          (decode every pattern of up to max_errors flipped bits, at most and by default 3)
+        - bench (simulated RAID 2 performance against other layouts)
+        - ls
+        - stat file_name
+        - checksum on|off (store CRC-32 of the files written next)
+        - log [last_events_number]
+        - logfile host_path (append events to the file as JSON lines)
+        - exit
+        Use arrows to edit the line and browse the history, Tab to complete.",
+        total_disks, total_disks
     );
 }
 
-fn invite_to_enter_command() {
-    print!("Your command: ");
-}
-
-fn say_error() {
-    println!("Invalid command. Try again, please!");
+// Reads lines until the one equal to `tag`, returns None if the input ends first
+fn read_heredoc(editor: &mut ReplEditor, tag: &str) -> Option<Vec<u8>> {
+    let mut lines = Vec::new();
+    loop {
+        match editor.readline(HEREDOC_PROMPT) {
+            Ok(line) => {
+                if line == tag {
+                    return Some(lines.join("\n").into_bytes());
+                }
+                lines.push(line);
+            }
+            Err(_) => return None,
+        }
+    }
 }
@@ -0,0 +1,440 @@
+//! A subset of the NBD protocol (https://github.com/NetworkBlockDevice/nbd/blob/master/doc/proto.md).
+//!
+//! Handshake: fixed newstyle, the only supported option is `NBD_OPT_EXPORT_NAME` (any export
+//! name is the array), the client can also abort. Transmission: `READ`, `WRITE`, `FLUSH` and
+//! `DISC` with byte offsets, plus `CMD_CORRUPT_DISK`, an extension which flips every bit of the
+//! disk whose number is sent in the offset field. Integers are big-endian, as in NBD.
+
+use crate::block_device::{BlockDevice, BlockError};
+use crate::raid::RaidII;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:10809";
+
+const NBD_MAGIC: u64 = 0x4e42444d41474943; // "NBDMAGIC"
+const IHAVEOPT: u64 = 0x49484156454f5054; // "IHAVEOPT"
+const REPLY_MAGIC: u64 = 0x0003e889045565a9;
+const REQUEST_MAGIC: u32 = 0x25609513;
+const SIMPLE_REPLY_MAGIC: u32 = 0x67446698;
+
+const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const FLAG_NO_ZEROES: u16 = 1 << 1;
+
+const FLAG_HAS_FLAGS: u16 = 1 << 0;
+const FLAG_SEND_FLUSH: u16 = 1 << 2;
+
+const OPT_EXPORT_NAME: u32 = 1;
+const OPT_ABORT: u32 = 2;
+const REP_ACK: u32 = 1;
+const REP_ERR_UNSUP: u32 = (1 << 31) + 1;
+
+const CMD_READ: u16 = 0;
+const CMD_WRITE: u16 = 1;
+const CMD_DISC: u16 = 2;
+const CMD_FLUSH: u16 = 3;
+pub const CMD_CORRUPT_DISK: u16 = 0x8000;
+
+const EPERM: u32 = 1;
+const EIO: u32 = 5;
+const EINVAL: u32 = 22;
+const ENOSPC: u32 = 28;
+
+// Requests longer than this are refused instead of allocating the buffer
+const MAX_REQUEST_LENGTH: u32 = 32 * 1024 * 1024;
+
+/// Serves the clients one after another, forever
+pub fn serve(raid: &mut RaidII, listener: &TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        println!("Client {} connected.", peer);
+        match handle_client(raid, stream) {
+            Ok(()) => println!("Client {} disconnected.", peer),
+            Err(error) => println!("Client {} dropped: {}", peer, error),
+        }
+    }
+    Ok(())
+}
+
+/// Runs the handshake and the transmission phase until the client disconnects
+pub fn handle_client(raid: &mut RaidII, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    write_u64(&mut stream, NBD_MAGIC)?;
+    write_u64(&mut stream, IHAVEOPT)?;
+    write_u16(&mut stream, FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES)?;
+    let client_flags = read_u32(&mut stream)?;
+    let no_zeroes = client_flags & FLAG_NO_ZEROES as u32 != 0;
+
+    loop {
+        if read_u64(&mut stream)? != IHAVEOPT {
+            return Err(invalid_data("wrong option magic"));
+        }
+        let option = read_u32(&mut stream)?;
+        let length = read_u32(&mut stream)?;
+        if length > MAX_REQUEST_LENGTH {
+            return Err(invalid_data("option data is too long"));
+        }
+        // The export name is not used, there is a single export
+        read_bytes(&mut stream, length as usize)?;
+
+        match option {
+            OPT_EXPORT_NAME => {
+                write_u64(&mut stream, device_size(raid))?;
+                write_u16(&mut stream, FLAG_HAS_FLAGS | FLAG_SEND_FLUSH)?;
+                if !no_zeroes {
+                    stream.write_all(&[0; 124])?;
+                }
+                return transmission(raid, &mut stream);
+            }
+            OPT_ABORT => {
+                write_option_reply(&mut stream, option, REP_ACK)?;
+                return Ok(());
+            }
+            _ => write_option_reply(&mut stream, option, REP_ERR_UNSUP)?,
+        }
+    }
+}
+
+fn transmission(raid: &mut RaidII, stream: &mut TcpStream) -> io::Result<()> {
+    loop {
+        if read_u32(stream)? != REQUEST_MAGIC {
+            return Err(invalid_data("wrong request magic"));
+        }
+        let _flags = read_u16(stream)?;
+        let command = read_u16(stream)?;
+        let handle = read_u64(stream)?;
+        let offset = read_u64(stream)?;
+        let length = read_u32(stream)?;
+        if length > MAX_REQUEST_LENGTH {
+            return Err(invalid_data("request is too long"));
+        }
+
+        match command {
+            CMD_READ => match read_bytes_at(raid, offset, length as usize) {
+                Ok(data) => {
+                    write_simple_reply(stream, 0, handle)?;
+                    stream.write_all(&data)?;
+                }
+                Err(error) => write_simple_reply(stream, error_code(&error, EINVAL), handle)?,
+            },
+            CMD_WRITE => {
+                let data = read_bytes(stream, length as usize)?;
+                let error = match write_bytes_at(raid, offset, &data) {
+                    Ok(()) => 0,
+                    Err(error) => error_code(&error, ENOSPC),
+                };
+                write_simple_reply(stream, error, handle)?;
+            }
+            // Writes go to the disks right away
+            CMD_FLUSH => write_simple_reply(stream, 0, handle)?,
+            CMD_DISC => return Ok(()),
+            CMD_CORRUPT_DISK => {
+                let corrupted =
+                    usize::try_from(offset).is_ok_and(|disk_number| raid.corrupt_disk(disk_number));
+                write_simple_reply(stream, if corrupted { 0 } else { EINVAL }, handle)?;
+            }
+            _ => write_simple_reply(stream, EINVAL, handle)?,
+        }
+        stream.flush()?;
+    }
+}
+
+fn device_size(device: &dyn BlockDevice) -> u64 {
+    device.block_count() * device.block_size() as u64
+}
+
+fn error_code(error: &BlockError, out_of_range: u32) -> u32 {
+    match error {
+        BlockError::Uncorrectable { .. } => EIO,
+        BlockError::FileSpace { .. } => EPERM,
+        BlockError::OutOfRange { .. } | BlockError::WrongBufferSize { .. } => out_of_range,
+    }
+}
+
+// Byte ranges are mapped to the blocks which they cover
+fn block_range(
+    device: &dyn BlockDevice,
+    offset: u64,
+    length: usize,
+) -> Result<(u64, u64), BlockError> {
+    let block_size = device.block_size() as u64;
+    let end = offset.checked_add(length as u64);
+    match end {
+        Some(end) if end <= device_size(device) => {
+            Ok((offset / block_size, end.div_ceil(block_size)))
+        }
+        _ => Err(BlockError::OutOfRange {
+            lba: offset / block_size,
+            block_count: device.block_count(),
+        }),
+    }
+}
+
+fn read_bytes_at(
+    device: &mut dyn BlockDevice,
+    offset: u64,
+    length: usize,
+) -> Result<Vec<u8>, BlockError> {
+    let (first, end) = block_range(device, offset, length)?;
+    let block_size = device.block_size();
+    let mut data = vec![0; (end - first) as usize * block_size];
+    for (lba, block) in (first..end).zip(data.chunks_mut(block_size)) {
+        device.read_block(lba, block)?;
+    }
+
+    let skipped = (offset - first * block_size as u64) as usize;
+    Ok(data[skipped..skipped + length].to_vec())
+}
+
+// Blocks which are only partly written are read first
+fn write_bytes_at(
+    device: &mut dyn BlockDevice,
+    offset: u64,
+    data: &[u8],
+) -> Result<(), BlockError> {
+    let (first, end) = block_range(device, offset, data.len())?;
+    let block_size = device.block_size() as u64;
+    let mut block = vec![0; block_size as usize];
+    for lba in first..end {
+        let block_start = lba * block_size;
+        let from = offset.max(block_start);
+        let to = (offset + data.len() as u64).min(block_start + block_size);
+        if to - from < block_size {
+            device.read_block(lba, &mut block)?;
+        }
+        block[(from - block_start) as usize..(to - block_start) as usize]
+            .copy_from_slice(&data[(from - offset) as usize..(to - offset) as usize]);
+        device.write_block(lba, &block)?;
+    }
+    Ok(())
+}
+
+/// Client of the server above, used by tests and for scripting the array
+pub struct NbdClient {
+    stream: TcpStream,
+    size: u64,
+    next_handle: u64,
+}
+
+impl NbdClient {
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        if read_u64(&mut stream)? != NBD_MAGIC || read_u64(&mut stream)? != IHAVEOPT {
+            return Err(invalid_data("the server does not speak newstyle NBD"));
+        }
+        let server_flags = read_u16(&mut stream)?;
+        if server_flags & FLAG_FIXED_NEWSTYLE == 0 {
+            return Err(invalid_data("the server does not support fixed newstyle"));
+        }
+        let no_zeroes = server_flags & FLAG_NO_ZEROES != 0;
+        let client_flags = if no_zeroes {
+            FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES
+        } else {
+            FLAG_FIXED_NEWSTYLE
+        };
+        write_u32(&mut stream, client_flags as u32)?;
+
+        write_u64(&mut stream, IHAVEOPT)?;
+        write_u32(&mut stream, OPT_EXPORT_NAME)?;
+        write_u32(&mut stream, 0)?;
+        let size = read_u64(&mut stream)?;
+        let _transmission_flags = read_u16(&mut stream)?;
+        if !no_zeroes {
+            read_bytes(&mut stream, 124)?;
+        }
+
+        Ok(NbdClient {
+            stream,
+            size,
+            next_handle: 0,
+        })
+    }
+
+    /// Size of the export in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn read(&mut self, offset: u64, length: u32) -> io::Result<Vec<u8>> {
+        self.request(CMD_READ, offset, length, &[])?;
+        read_bytes(&mut self.stream, length as usize)
+    }
+
+    pub fn write(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let length = u32::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "data is too long"))?;
+        self.request(CMD_WRITE, offset, length, data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.request(CMD_FLUSH, 0, 0, &[])
+    }
+
+    pub fn corrupt_disk(&mut self, disk_number: u64) -> io::Result<()> {
+        self.request(CMD_CORRUPT_DISK, disk_number, 0, &[])
+    }
+
+    pub fn disconnect(mut self) -> io::Result<()> {
+        // There is no reply to a disconnect
+        self.send_request(CMD_DISC, 0, 0, &[]).map(|_| ())
+    }
+
+    fn send_request(
+        &mut self,
+        command: u16,
+        offset: u64,
+        length: u32,
+        data: &[u8],
+    ) -> io::Result<u64> {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+
+        let mut request = Vec::with_capacity(28 + data.len());
+        request.extend_from_slice(&REQUEST_MAGIC.to_be_bytes());
+        request.extend_from_slice(&0u16.to_be_bytes());
+        request.extend_from_slice(&command.to_be_bytes());
+        request.extend_from_slice(&handle.to_be_bytes());
+        request.extend_from_slice(&offset.to_be_bytes());
+        request.extend_from_slice(&length.to_be_bytes());
+        request.extend_from_slice(data);
+        self.stream.write_all(&request)?;
+        Ok(handle)
+    }
+
+    // Sends the request and waits for its reply, the data of a read follows the reply
+    fn request(&mut self, command: u16, offset: u64, length: u32, data: &[u8]) -> io::Result<()> {
+        let handle = self.send_request(command, offset, length, data)?;
+        if read_u32(&mut self.stream)? != SIMPLE_REPLY_MAGIC {
+            return Err(invalid_data("wrong reply magic"));
+        }
+        let error = read_u32(&mut self.stream)?;
+        if read_u64(&mut self.stream)? != handle {
+            return Err(invalid_data("reply to another request"));
+        }
+        match error {
+            0 => Ok(()),
+            EIO => Err(io::Error::other("the server can not decode the data")),
+            EINVAL | ENOSPC => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the server refused the request with error {}", error),
+            )),
+            _ => Err(io::Error::other(format!("the server error {}", error))),
+        }
+    }
+}
+
+fn write_option_reply(stream: &mut TcpStream, option: u32, reply_type: u32) -> io::Result<()> {
+    write_u64(stream, REPLY_MAGIC)?;
+    write_u32(stream, option)?;
+    write_u32(stream, reply_type)?;
+    write_u32(stream, 0)
+}
+
+fn write_simple_reply(stream: &mut TcpStream, error: u32, handle: u64) -> io::Result<()> {
+    write_u32(stream, SIMPLE_REPLY_MAGIC)?;
+    write_u32(stream, error)?;
+    write_u64(stream, handle)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_bytes(stream: &mut impl Read, length: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; length];
+    stream.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u16(stream: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    stream.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32(stream: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(stream: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    stream.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn write_u16(stream: &mut impl Write, value: u16) -> io::Result<()> {
+    stream.write_all(&value.to_be_bytes())
+}
+
+fn write_u32(stream: &mut impl Write, value: u32) -> io::Result<()> {
+    stream.write_all(&value.to_be_bytes())
+}
+
+fn write_u64(stream: &mut impl Write, value: u64) -> io::Result<()> {
+    stream.write_all(&value.to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbd::*;
+    use crate::raid::DEFAULT_BLOCK_SIZE;
+    use std::thread;
+
+    // Serves one client on a free port, the array is returned when the client disconnects
+    fn start_server(raid: RaidII) -> (u16, thread::JoinHandle<RaidII>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut raid = raid;
+            let (stream, _) = listener.accept().unwrap();
+            handle_client(&mut raid, stream).unwrap();
+            raid
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn write_corrupt_read_test() {
+        let (port, server) = start_server(RaidII::from_data_capacity(1024));
+        let mut client = NbdClient::connect(("127.0.0.1", port)).unwrap();
+        assert_eq!(client.size(), 26 * DEFAULT_BLOCK_SIZE as u64);
+
+        // Crosses the border of two blocks
+        let data = b"Hello over the network!";
+        client.write(500, data).unwrap();
+        client.flush().unwrap();
+        client.corrupt_disk(5).unwrap();
+        assert_eq!(client.read(500, data.len() as u32).unwrap(), data);
+        assert_eq!(client.read(0, 4).unwrap(), [0; 4]);
+
+        client.corrupt_disk(1).unwrap();
+        client.corrupt_disk(2).unwrap();
+        assert!(client.read(500, 1).is_err());
+        assert!(client.corrupt_disk(14).is_err());
+        assert!(client.write(client.size() - 1, b"ab").is_err());
+        client.disconnect().unwrap();
+
+        let raid = server.join().unwrap();
+        // Both blocks under the data are read, and corrected, as a whole
+        assert_eq!(
+            raid.status().disks[4].corrected_errors,
+            2 * DEFAULT_BLOCK_SIZE
+        );
+    }
+
+    #[test]
+    fn byte_ranges_test() {
+        let mut raid = RaidII::from_data_capacity(1024);
+        let data: Vec<u8> = (0..1500).map(|i| i as u8).collect();
+        write_bytes_at(&mut raid, 100, &data).unwrap();
+        assert_eq!(read_bytes_at(&mut raid, 100, 1500).unwrap(), data);
+        assert_eq!(read_bytes_at(&mut raid, 99, 2).unwrap(), [0, 0]);
+        assert_eq!(read_bytes_at(&mut raid, 1599, 2).unwrap(), [219, 0]);
+        let size = device_size(&raid);
+        assert!(read_bytes_at(&mut raid, size, 1).is_err());
+    }
+}
@@ -1,10 +1,22 @@
+use crate::block_device::BlockError;
+use crate::checksum::crc32;
+use crate::disk::Disk;
+use crate::event_log::{Event, EventKind, EventLog};
+use crate::file_handle::RaidFile;
 use crate::hamming_encoding::*;
-
-type Disk = Vec<Bit>;
+use crate::timing::{self, DiskTiming};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
 
 pub struct RaidII {
-    // Disks
-    parity_bit_disk: Disk,
+    code: HammingCode,
+
+    // Disks, there is no parity bit disk if the code is not extended
+    parity_bit_disk: Option<Disk>,
     data_bit_disks: Vec<Disk>,
     hamming_bit_disks: Vec<Disk>,
 
@@ -12,100 +24,495 @@ pub struct RaidII {
     total_capcity: usize,
     disk_size: usize,
     free_space: usize,
+    block_size: usize,
+    // Blocks written by `write_block`, files are not stored over them
+    written_blocks: BTreeSet<u64>,
+    files: Vec<File>,
+    checksums: bool,
+
+    // Health, indexed by the position of the disk in the codeword
+    disk_statuses: Vec<DiskStatus>,
+    suspect_threshold: usize,
+    rebuild: Option<RebuildProgress>,
+    // Spare disks which replace failed or suspect disks without waiting for the user
+    hot_spares: usize,
+    // Disks waiting for a spare until the rebuild in progress is finished
+    failover_queue: Vec<usize>,
+
+    events: EventLog,
+
+    // Disk number `n` is at index `n - 1`
+    timings: Vec<DiskTiming>,
+    // Fraction of a rotation every spindle is ahead of the synchronized position
+    spindle_offsets: Vec<f64>,
+    // Simulated time spent by the timed operations
+    clock: Duration,
+
+    snapshots: BTreeMap<usize, Snapshot>,
+    next_snapshot_id: usize,
+    // States before the operations which can be undone, with the name of the operation
+    undo_journal: Vec<(String, Snapshot)>,
+    // States before the undone operations were undone
+    redo_journal: Vec<(String, Snapshot)>,
+
+    reshape: Option<Reshape>,
+}
+
+// The new geometry being filled by `RaidII::reshape_step`, the old one serves all reads
+// and writes until every stripe is copied
+struct Reshape {
+    code: HammingCode,
+    // Disk number `n` is at index `n - 1`, like in the codeword
+    disks: Vec<Disk>,
+    capacity: usize,
+    progress: ReshapeProgress,
+}
+
+// State of the array saved by `RaidII::snapshot` and the undo journal, the disks share
+// their chunks with the array until either of them is written
+struct Snapshot {
+    parity_bit_disk: Option<Disk>,
+    data_bit_disks: Vec<Disk>,
+    hamming_bit_disks: Vec<Disk>,
+    free_space: usize,
+    written_blocks: BTreeSet<u64>,
     files: Vec<File>,
+    disk_statuses: Vec<DiskStatus>,
+    rebuild: Option<RebuildProgress>,
+    hot_spares: usize,
+    failover_queue: Vec<usize>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct File {
     name: String,
     start_pos: usize,
     end_pos: usize,
     size: usize,
     file_type: FileType,
+    // CRC-32 of the content, checked on every read
+    checksum: Option<u32>,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum FileType {
     Text,
 }
 
+/// How many stripe positions of a file decode cleanly, with a correctable error or not at all
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FileHealth {
+    pub clean: usize,
+    pub correctable: usize,
+    pub uncorrectable: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum FileWriteResult {
     Success,
     NotEnoughSpace,
 }
 
+/// What `RaidII::journaled` looks at to decide whether an operation changed the array
+pub trait Succeeded {
+    fn succeeded(&self) -> bool;
+}
+
+impl Succeeded for bool {
+    fn succeeded(&self) -> bool {
+        *self
+    }
+}
+
+impl Succeeded for FileWriteResult {
+    fn succeeded(&self) -> bool {
+        *self == FileWriteResult::Success
+    }
+}
+
+impl<T, E> Succeeded for Result<T, E> {
+    fn succeeded(&self) -> bool {
+        self.is_ok()
+    }
+}
+
+// A result with something on the side, as the time of `write_file_timed`
+impl<T: Succeeded, U> Succeeded for (T, U) {
+    fn succeeded(&self) -> bool {
+        self.0.succeeded()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum FileReadResult {
     NotFound,
     DisksCorrupted,
+    // Every stripe was decoded, but the content does not match the file checksum
+    SilentCorruptionDetected,
     Success(FileType, Vec<u8>),
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum CreateError {
+    // A handle finds its file by the position, a second file of the name could not be read
+    NameTaken,
+    NotEnoughSpace,
+    Busy,
+}
+
+impl fmt::Display for CreateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateError::NameTaken => write!(f, "there is a file with this name already"),
+            CreateError::NotEnoughSpace => write!(f, "the array is full"),
+            CreateError::Busy => write!(f, "a disk is being rebuilt"),
+        }
+    }
+}
+
+impl std::error::Error for CreateError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArrayState {
+    Optimal,
+    // A disk has failed or is suspect, one more fault can not be corrected
+    Degraded,
+    Rebuilding,
+    // Stripes are being copied to a different number of data disks
+    Reshaping,
+    // More disks have failed than the code is able to correct
+    Failed,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiskState {
+    Online,
+    Failed,
+    // Online, but more errors than `suspect_threshold` were corrected on the disk
+    Suspect,
+    // Swapped for a new disk, its data is restored from the other disks
+    Replaced,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiskStatus {
+    pub state: DiskState,
+    pub corrected_errors: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RebuildProgress {
+    pub disk_number: usize,
+    pub rebuilt_positions: usize,
+    pub total_positions: usize,
+    // Positions where other disks are damaged too, so the bit could not be restored
+    pub lost_positions: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReshapeProgress {
+    pub from_data_disks: usize,
+    pub to_data_disks: usize,
+    // Bytes of the address space already encoded with the new number of data disks
+    pub reshaped_bytes: usize,
+    pub total_bytes: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArrayStatus {
+    pub state: ArrayState,
+    // Disk number `n` is at index `n - 1`
+    pub disks: Vec<DiskStatus>,
+    pub rebuild: Option<RebuildProgress>,
+    pub hot_spares: usize,
+    pub reshape: Option<ReshapeProgress>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GeometryError {
+    // Every stripe holds whole bytes, so the number of data disks is a multiple of 8
+    NotWholeBytes { data_disks: usize },
+    // The files and the written blocks take more space than the array would have
+    NotEnoughSpace { needed: usize, capacity: usize },
+    // A rebuild or a reshape to another number of data disks is in progress
+    Busy,
+    // A stripe of a file or of a written block can not be decoded, copying it would turn it
+    // into valid zeros
+    Uncorrectable { position: usize },
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeometryError::NotWholeBytes { data_disks } => write!(
+                f,
+                "{} data disks do not hold whole bytes, use a multiple of 8",
+                data_disks
+            ),
+            GeometryError::NotEnoughSpace { needed, capacity } => write!(
+                f,
+                "the data takes {} bytes, but the array would only have {}",
+                needed, capacity
+            ),
+            GeometryError::Busy => write!(f, "another rebuild or reshape is in progress"),
+            GeometryError::Uncorrectable { position } => write!(
+                f,
+                "byte {} can not be decoded, delete its file or write its block again to go on",
+                position
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeleteError {
+    NotFound,
+    // The stripes can not be moved while a disk is being rebuilt
+    Busy,
+    // A stripe after the file can not be decoded, moving it would turn it into valid zeros
+    Uncorrectable { position: usize },
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteError::NotFound => write!(f, "there is no such file"),
+            DeleteError::Busy => write!(f, "a disk is being rebuilt"),
+            DeleteError::Uncorrectable { position } => write!(
+                f,
+                "byte {} of a later file can not be decoded, delete that file first",
+                position
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DeleteError {}
+
+pub const DEFAULT_SUSPECT_THRESHOLD: usize = 16;
+
+pub const DEFAULT_BLOCK_SIZE: usize = 512;
+
+// Positions restored by a rebuild in progress with every file read or write
+pub const BACKGROUND_REBUILD_POSITIONS: usize = 256;
+
+// Operations kept in the undo journal, older ones can not be undone anymore
+pub const JOURNAL_LENGTH: usize = 100;
+
+struct DecodedRange {
+    bytes: Vec<u8>,
+    // Single invalid bits found while decoding: (position of the disk in the codeword, stripe)
+    corrections: Vec<(usize, usize)>,
+    invalid_position: Option<usize>,
+}
+
+pub(crate) enum DecodedFile {
+    NotFound,
+    Uncorrectable {
+        position: usize,
+    },
+    ChecksumMismatch,
+    Decoded {
+        file_type: FileType,
+        bytes: Vec<u8>,
+        corrections: Vec<(usize, usize)>,
+    },
+}
+
 enum ReadData<T> {
-    ValidData(T),
-    CorruptedData {
+    Valid(T),
+    Corrupted {
         data: T,
+        // Position of the invalid bit in the codeword, that is disk number - 1
         disk_number: usize,
         bit_number: usize,
     },
-    InvalidData,
+    Invalid,
+}
+
+impl File {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn start_pos(&self) -> usize {
+        self.start_pos
+    }
+
+    pub fn end_pos(&self) -> usize {
+        self.end_pos
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn checksum(&self) -> Option<u32> {
+        self.checksum
+    }
 }
 
 impl RaidII {
+    /// RAID II with 13 disks, which corrects one error and detects two
     pub fn from_data_capacity(disk_size: usize) -> Self {
-        let mut hamming_disks = 0;
-        let capacity = 8;
-        while (capacity + hamming_disks + 1) > (1 << hamming_disks) {
-            hamming_disks += 1;
+        Self::new(disk_size, true)
+    }
+
+    /// Every byte is stored as a codeword of the Hamming code, one bit per disk.
+    /// Without the whole block parity bit (`extended == false`) the array has 12 disks,
+    /// it still corrects one error, but two errors are mistaken for one and "corrected" wrong.
+    pub fn new(disk_size: usize, extended: bool) -> Self {
+        match Self::with_data_disks(disk_size, 8, extended) {
+            Ok(raid_ii) => raid_ii,
+            Err(_) => unreachable!("8 data disks always hold whole bytes"),
         }
+    }
+
+    /// Array which stores `data_disks / 8` bytes in every stripe, with as many Hamming
+    /// disks as the code needs: 16 data disks take 5 of them, 64 data disks take 7.
+    pub fn with_data_disks(
+        disk_size: usize,
+        data_disks: usize,
+        extended: bool,
+    ) -> Result<Self, GeometryError> {
+        let code = Self::stripe_code(data_disks, extended)?;
+        let capacity = code.data_bits();
+        let hamming_disks = code.parity_bits();
 
         let mut data_bit_disks = Vec::with_capacity(capacity);
         let mut hamming_bit_disks = Vec::with_capacity(hamming_disks);
         let files = Vec::new();
 
         for _ in 0..capacity {
-            data_bit_disks.push(vec![]);
+            data_bit_disks.push(Disk::new());
         }
 
         for _ in 0..hamming_disks {
-            hamming_bit_disks.push(vec![]);
+            hamming_bit_disks.push(Disk::new());
         }
 
-        let total_disks = 1 + capacity + hamming_disks;
+        let total_disks = code.codeword_len();
 
-        RaidII {
-            parity_bit_disk: Vec::new(),
+        Ok(RaidII {
+            code,
+            parity_bit_disk: if extended { Some(Disk::new()) } else { None },
             data_bit_disks,
             hamming_bit_disks,
             total_disks,
             disk_size,
             free_space: disk_size * total_disks,
+            block_size: DEFAULT_BLOCK_SIZE,
+            written_blocks: BTreeSet::new(),
             files,
+            checksums: true,
             total_capcity: disk_size * total_disks,
+            disk_statuses: vec![
+                DiskStatus {
+                    state: DiskState::Online,
+                    corrected_errors: 0,
+                };
+                total_disks
+            ],
+            suspect_threshold: DEFAULT_SUSPECT_THRESHOLD,
+            rebuild: None,
+            hot_spares: 0,
+            failover_queue: Vec::new(),
+            events: EventLog::new(),
+            timings: vec![DiskTiming::default(); total_disks],
+            spindle_offsets: vec![0.0; total_disks],
+            clock: Duration::ZERO,
+            snapshots: BTreeMap::new(),
+            next_snapshot_id: 1,
+            undo_journal: Vec::new(),
+            redo_journal: Vec::new(),
+            reshape: None,
+        })
+    }
+
+    fn stripe_code(data_disks: usize, extended: bool) -> Result<HammingCode, GeometryError> {
+        if data_disks == 0 || !data_disks.is_multiple_of(8) {
+            return Err(GeometryError::NotWholeBytes { data_disks });
         }
+        HammingCode::with_data_bits(data_disks, extended)
+            .map_err(|_| GeometryError::NotWholeBytes { data_disks })
     }
 
-    pub fn write_file(
-        &mut self,
-        data: &Vec<u8>,
-        file_type: FileType,
-        name: &String,
-    ) -> FileWriteResult {
+    // Bytes stored by one bit of every disk
+    fn bytes_per_stripe(&self) -> usize {
+        self.code.data_bits() / 8
+    }
+
+    pub fn code(&self) -> HammingCode {
+        self.code
+    }
+
+    pub fn total_disks(&self) -> usize {
+        self.total_disks
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &File> {
+        self.files.iter()
+    }
+
+    /// Bytes which can still be written to files. Files end before the first block written
+    /// by `write_block`, and during a reshape they must also fit the new geometry, which may
+    /// be smaller.
+    pub fn free_space(&self) -> usize {
+        let used = self.total_capcity - self.free_space;
+        let mut free_space = self.free_space;
+        if let Some(lba) = self.written_blocks.first() {
+            free_space = free_space.min((*lba as usize * self.block_size).saturating_sub(used));
+        }
+        if let Some(reshape) = &self.reshape {
+            free_space = free_space.min(reshape.capacity.saturating_sub(used));
+        }
+        free_space
+    }
+
+    /// Decodes every stripe position of the file without repairing anything
+    pub fn file_health(&self, name: &str) -> Option<FileHealth> {
+        let file = self.files.iter().find(|x| x.name == name)?;
+        let mut health = FileHealth::default();
+        let bytes_per_stripe = self.bytes_per_stripe();
+        for stripe in file.start_pos / bytes_per_stripe..file.end_pos.div_ceil(bytes_per_stripe) {
+            match self.read_stripe(stripe) {
+                ReadData::Valid(_) => health.clean += 1,
+                ReadData::Corrupted { .. } => health.correctable += 1,
+                ReadData::Invalid => health.uncorrectable += 1,
+            }
+        }
+        Some(health)
+    }
+
+    pub fn write_file(&mut self, data: &[u8], file_type: FileType, name: &str) -> FileWriteResult {
         match file_type {
             FileType::Text => {
-                if self.free_space > data.len() {
-                    for byte in data {
-                        self.write_byte(*byte)
-                    }
+                if self.free_space() > data.len() {
+                    let start_pos = self.total_capcity - self.free_space;
+                    self.write_bytes(start_pos, data);
 
                     let file = File {
-                        name: name.clone(),
-                        start_pos: self.total_capcity - self.free_space,
+                        name: name.to_owned(),
+                        start_pos,
                         end_pos: self.total_capcity - self.free_space + data.len(),
                         size: data.len(),
                         file_type,
+                        checksum: self.checksums.then(|| crc32(data)),
                     };
 
                     self.free_space -= data.len();
                     self.files.push(file);
+                    self.events.record(EventKind::FileWritten {
+                        name: name.to_owned(),
+                        size: data.len(),
+                    });
+                    self.rebuild_in_background();
 
                     FileWriteResult::Success
                 } else {
@@ -115,160 +522,1246 @@ impl RaidII {
         }
     }
 
-    fn write_byte(&mut self, byte: u8) {
-        let bits = &bit_vector_from_bytes(&vec![byte]);
-        let mut written_bit_counter = 0;
-        let encoded_bits = encode(&bits);
-        self.parity_bit_disk.push(encoded_bits[written_bit_counter]);
-        written_bit_counter += 1;
-
-        for disk in &mut self.data_bit_disks {
-            disk.push(encoded_bits[written_bit_counter]);
-            written_bit_counter += 1;
-        }
-
-        for disk in &mut self.hamming_bit_disks {
-            disk.push(encoded_bits[written_bit_counter]);
-            written_bit_counter += 1;
-        }
-    }
-
-    pub fn read_file(&mut self, name: &String) -> FileReadResult {
-        let mut invalid_data = false;
-        match self.files.iter().find(|x| (*x).name == *name) {
-            Some(file) => {
-                let mut bytes = Vec::with_capacity(file.size);
-                for position in file.start_pos..file.end_pos {
-                    match self.read_byte(position) {
-                        ReadData::ValidData(byte) => bytes.push(byte),
-                        ReadData::CorruptedData {
-                            data,
-                            bit_number,
-                            disk_number,
-                        } => {
-                            // Restore invalid bit
-                            match disk_number {
-                                0 => {
-                                    self.parity_bit_disk[bit_number] =
-                                        !self.parity_bit_disk[bit_number];
-                                }
-                                0..=8 => {
-                                    self.data_bit_disks[disk_number - 1][bit_number] =
-                                        !self.data_bit_disks[disk_number - 1][bit_number];
-                                }
-                                _ => {
-                                    let offset = 1 + self.data_bit_disks.len();
-                                    self.hamming_bit_disks[disk_number - offset][bit_number] =
-                                        !self.hamming_bit_disks[disk_number - offset][bit_number];
-                                }
-                            }
-                            bytes.push(data)
-                        }
-                        ReadData::InvalidData => {
-                            invalid_data = true;
-                            break;
-                        }
-                    }
+    /// Removes the file and moves the stripes of the files written after it down, so the
+    /// space can be used again. Not possible while a disk is being rebuilt, nor when the
+    /// later files have to be decoded to be moved and one of their stripes can not be.
+    pub fn delete_file(&mut self, name: &str) -> Result<(), DeleteError> {
+        let Some(index) = self.files.iter().position(|file| file.name == name) else {
+            return Err(DeleteError::NotFound);
+        };
+        if self.rebuild.is_some() {
+            return Err(DeleteError::Busy);
+        }
+
+        let (start_pos, end_pos) = (self.files[index].start_pos, self.files[index].end_pos);
+        let length = end_pos - start_pos;
+        let used = self.total_capcity - self.free_space;
+        let bytes_per_stripe = self.bytes_per_stripe();
+        // The last stripe of the files may also hold the start of a block, which stays
+        if start_pos.is_multiple_of(bytes_per_stripe)
+            && length.is_multiple_of(bytes_per_stripe)
+            && used.is_multiple_of(bytes_per_stripe)
+        {
+            // Whole stripes are moved as they are, errors included
+            let (start, shift) = (start_pos / bytes_per_stripe, length / bytes_per_stripe);
+            for disk_number in 1..=self.total_disks {
+                let disk = self.disk_mut(disk_number);
+                let stored = disk.len().min(used.div_ceil(bytes_per_stripe));
+                for position in start..stored.saturating_sub(shift) {
+                    disk[position] = disk[position + shift];
+                }
+                for position in stored.saturating_sub(shift).max(start)..stored {
+                    disk[position] = false;
+                }
+            }
+        } else {
+            // The later files share stripes with other data after the move, so they are
+            // decoded and encoded again
+            let decoded = self.decode_range(end_pos, used - end_pos);
+            if let Some(position) = decoded.invalid_position {
+                self.events.record(EventKind::Uncorrectable { position });
+                return Err(DeleteError::Uncorrectable { position });
+            }
+            let mut moved = decoded.bytes;
+            moved.resize(used - start_pos, 0);
+            self.write_bytes(start_pos, &moved);
+        }
+        let file = self.files.remove(index);
+        self.rewind_reshape(start_pos);
+        for later in &mut self.files[index..] {
+            later.start_pos -= length;
+            later.end_pos -= length;
+        }
+        self.free_space += length;
+        self.events.record(EventKind::FileDeleted {
+            name: file.name,
+            size: file.size,
+        });
+        Ok(())
+    }
+
+    /// Size of a block of the block device, in bytes
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Blocks of the whole address space, the ones which hold files can not be accessed.
+    /// During a reshape the blocks must also fit the new geometry.
+    pub fn block_count(&self) -> u64 {
+        let capacity = match &self.reshape {
+            Some(reshape) => self.total_capcity.min(reshape.capacity),
+            None => self.total_capcity,
+        };
+        (capacity / self.block_size) as u64
+    }
+
+    /// Reads the block and restores single invalid bits in it.
+    /// Blocks which were never written are read as zeros.
+    pub fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let start = self.block_start(lba, buf.len())?;
+        let decoded = self.decode_range(start, buf.len());
+        if let Some(position) = decoded.invalid_position {
+            self.events.record(EventKind::Uncorrectable { position });
+            return Err(BlockError::Uncorrectable { lba, position });
+        }
+
+        self.restore_bits(&decoded.corrections);
+        buf.copy_from_slice(&decoded.bytes);
+        Ok(())
+    }
+
+    pub fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let start = self.block_start(lba, buf.len())?;
+        self.write_bytes(start, buf);
+        self.written_blocks.insert(lba);
+        Ok(())
+    }
+
+    fn block_start(&self, lba: u64, buffer_size: usize) -> Result<usize, BlockError> {
+        if buffer_size != self.block_size {
+            return Err(BlockError::WrongBufferSize {
+                expected: self.block_size,
+                actual: buffer_size,
+            });
+        }
+        if lba >= self.block_count() {
+            return Err(BlockError::OutOfRange {
+                lba,
+                block_count: self.block_count(),
+            });
+        }
+        // Blocks never share bytes with the files, so neither of them overwrites the other
+        let start = lba as usize * self.block_size;
+        if start < self.total_capcity - self.free_space {
+            return Err(BlockError::FileSpace { lba });
+        }
+        Ok(start)
+    }
+
+    // End of the bytes which must survive a reshape: the files and the written blocks
+    fn data_end(&self) -> usize {
+        let used = self.total_capcity - self.free_space;
+        let blocks_end = self
+            .written_blocks
+            .last()
+            .map_or(0, |lba| (*lba as usize + 1) * self.block_size);
+        used.max(blocks_end)
+    }
+
+    // Whether the byte belongs to a file or to a written block
+    fn holds_data(&self, position: usize) -> bool {
+        position < self.total_capcity - self.free_space
+            || self
+                .written_blocks
+                .contains(&((position / self.block_size) as u64))
+    }
+
+    // Files and blocks are ranges of the same address space, a stripe holds
+    // `bytes_per_stripe` consecutive bytes. The other bytes of a stripe which is only
+    // partly written are decoded first, with one byte per stripe there are none.
+    fn write_bytes(&mut self, start: usize, data: &[u8]) {
+        let bytes_per_stripe = self.bytes_per_stripe();
+        let end = start + data.len();
+        let stripes = end.div_ceil(bytes_per_stripe);
+        if self.disk(1).len() < stripes {
+            // Stripes which were never written hold the codeword of zero, that is zero bits
+            for disk_number in 1..=self.total_disks {
+                self.disk_mut(disk_number).resize(stripes, false);
+            }
+        }
+
+        for stripe in start / bytes_per_stripe..stripes {
+            let stripe_start = stripe * bytes_per_stripe;
+            let mut bytes = if start <= stripe_start && stripe_start + bytes_per_stripe <= end {
+                vec![0; bytes_per_stripe]
+            } else {
+                match self.read_stripe(stripe) {
+                    ReadData::Valid(bytes) | ReadData::Corrupted { data: bytes, .. } => bytes,
+                    // The rest of the stripe is lost already
+                    ReadData::Invalid => vec![0; bytes_per_stripe],
+                }
+            };
+            for (index, byte) in bytes.iter_mut().enumerate() {
+                let position = stripe_start + index;
+                if start <= position && position < end {
+                    *byte = data[position - start];
                 }
+            }
+            for (written_bit_counter, bit) in self.encode_stripe(&bytes).into_iter().enumerate() {
+                self.disk_mut(written_bit_counter + 1)[stripe] = bit;
+            }
+        }
+        self.rewind_reshape(start);
+    }
 
-                if invalid_data {
-                    FileReadResult::DisksCorrupted
-                } else {
-                    FileReadResult::Success(file.file_type, bytes)
+    // Decodes the stripes without changing the disks, stops at the first invalid stripe
+    fn decode_range(&self, start: usize, length: usize) -> DecodedRange {
+        let mut decoded = DecodedRange {
+            bytes: Vec::with_capacity(length),
+            corrections: Vec::new(),
+            invalid_position: None,
+        };
+
+        let bytes_per_stripe = self.bytes_per_stripe();
+        let end = start + length;
+        let mut position = start;
+        while position < end {
+            let stripe = position / bytes_per_stripe;
+            let stripe_start = stripe * bytes_per_stripe;
+            let stripe_end = end.min(stripe_start + bytes_per_stripe);
+            let bytes = match self.read_stripe(stripe) {
+                ReadData::Valid(bytes) => bytes,
+                ReadData::Corrupted {
+                    data,
+                    bit_number,
+                    disk_number,
+                } => {
+                    decoded.corrections.push((disk_number, bit_number));
+                    data
+                }
+                ReadData::Invalid => {
+                    decoded.invalid_position = Some(position);
+                    break;
                 }
+            };
+            decoded
+                .bytes
+                .extend_from_slice(&bytes[position - stripe_start..stripe_end - stripe_start]);
+            position = stripe_end;
+        }
+        decoded
+    }
+
+    fn encode_stripe(&self, bytes: &[u8]) -> Vec<Bit> {
+        let bits = bit_vector_from_bytes(bytes);
+        let mut encoded_bits = match self.code.encode(&bits) {
+            Ok(encoded_bits) => encoded_bits,
+            Err(error) => unreachable!("the array code encodes whole stripes: {}", error),
+        };
+
+        // A failed disk does not store anything
+        for (bit, status) in encoded_bits.iter_mut().zip(&self.disk_statuses) {
+            *bit &= status.state != DiskState::Failed;
+        }
+        encoded_bits
+    }
+
+    /// Opens the file for streaming reads and writes
+    pub fn open(&mut self, name: &str) -> Option<RaidFile<'_>> {
+        let index = self.files.iter().position(|x| x.name == name)?;
+        Some(RaidFile::new(self, index))
+    }
+
+    /// Creates an empty file after the last one, the file grows while data is written to it.
+    /// Not possible while a disk is being rebuilt.
+    pub fn create(&mut self, name: &str, file_type: FileType) -> Result<RaidFile<'_>, CreateError> {
+        if self.files.iter().any(|file| file.name == name) {
+            return Err(CreateError::NameTaken);
+        }
+        // The file could not grow by a single byte
+        if self.free_space() <= 1 {
+            return Err(CreateError::NotEnoughSpace);
+        }
+        if self.rebuild.is_some() {
+            return Err(CreateError::Busy);
+        }
+        let start_pos = self.total_capcity - self.free_space;
+        self.files.push(File {
+            name: name.to_owned(),
+            start_pos,
+            end_pos: start_pos,
+            size: 0,
+            file_type,
+            checksum: self.checksums.then(|| crc32(&[])),
+        });
+        let index = self.files.len() - 1;
+        Ok(RaidFile::new(self, index))
+    }
+
+    pub(crate) fn file_at(&self, index: usize) -> &File {
+        &self.files[index]
+    }
+
+    // Stripes which hold the bytes of the file, the one `read_file` finds by the name
+    pub(crate) fn file_stripes(&self, name: &str) -> Option<Range<usize>> {
+        let file = self.files.iter().find(|x| x.name == name)?;
+        Some(self.stripes_of(file))
+    }
+
+    pub(crate) fn stripes_of(&self, file: &File) -> Range<usize> {
+        let bytes_per_stripe = self.bytes_per_stripe();
+        file.start_pos / bytes_per_stripe..file.end_pos.div_ceil(bytes_per_stripe)
+    }
+
+    pub(crate) fn stored_stripes(&self) -> usize {
+        self.disk(1).len()
+    }
+
+    // Bits of one disk at the stripes, stripes which were never written are zeros
+    pub(crate) fn column(&self, disk_number: usize, stripes: Range<usize>) -> Vec<Bit> {
+        let disk = self.disk(disk_number);
+        stripes
+            .map(|stripe| stripe < disk.len() && disk[stripe])
+            .collect()
+    }
+
+    pub(crate) fn set_column(&mut self, disk_number: usize, start: usize, bits: &[Bit]) {
+        // All disks keep the same length
+        if self.disk(1).len() < start + bits.len() {
+            for disk_number in 1..=self.total_disks {
+                self.disk_mut(disk_number).resize(start + bits.len(), false);
+            }
+        }
+        let disk = self.disk_mut(disk_number);
+        for (offset, bit) in bits.iter().enumerate() {
+            disk[start + offset] = *bit;
+        }
+    }
+
+    // Decodes the stripes without restoring anything: the corrections are only written back
+    // by `restore_bits` once the caller trusts them. Returns the bytes before the first
+    // stripe which can not be decoded, the position of that stripe and the corrections.
+    pub(crate) fn read_range(
+        &mut self,
+        start: usize,
+        length: usize,
+    ) -> (Vec<u8>, Option<usize>, Vec<(usize, usize)>) {
+        let decoded = self.decode_range(start, length);
+        if let Some(position) = decoded.invalid_position {
+            self.events.record(EventKind::Uncorrectable { position });
+        }
+        (decoded.bytes, decoded.invalid_position, decoded.corrections)
+    }
+
+    // Writes the byte at the offset of the file. The last file may grow by one byte,
+    // returns false if the file can not grow.
+    pub(crate) fn write_file_byte(&mut self, index: usize, offset: usize, byte: u8) -> bool {
+        let file = &self.files[index];
+        let position = file.start_pos + offset;
+        if offset < file.size {
+            self.write_bytes(position, &[byte]);
+            return true;
+        }
+
+        let is_last = index == self.files.len() - 1;
+        if offset > file.size || !is_last || self.free_space() <= 1 {
+            return false;
+        }
+        self.write_bytes(position, &[byte]);
+        self.free_space -= 1;
+        let file = &mut self.files[index];
+        file.end_pos += 1;
+        file.size += 1;
+        true
+    }
+
+    // Recomputes the checksum of the file after writes through a handle
+    pub(crate) fn finish_file_write(&mut self, index: usize) -> io::Result<()> {
+        let file = &self.files[index];
+        let (name, size) = (file.name.clone(), file.size);
+        if file.checksum.is_some() {
+            let decoded = self.decode_range(file.start_pos, file.size);
+            if decoded.invalid_position.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the file can not be decoded to compute its checksum",
+                ));
+            }
+            self.files[index].checksum = Some(crc32(&decoded.bytes));
+        }
+
+        self.events.record(EventKind::FileWritten { name, size });
+        Ok(())
+    }
+
+    pub fn read_file(&mut self, name: &str) -> FileReadResult {
+        let decoded = self.decode_file(name);
+        self.finish_read(name, decoded).0
+    }
+
+    // The part of `read_file` which does not change the array, so it can run in parallel
+    pub(crate) fn decode_file(&self, name: &str) -> DecodedFile {
+        let Some(file) = self.files.iter().find(|x| x.name == name) else {
+            return DecodedFile::NotFound;
+        };
+        let DecodedRange {
+            bytes,
+            corrections,
+            invalid_position,
+        } = self.decode_range(file.start_pos, file.size);
+        if let Some(position) = invalid_position {
+            return DecodedFile::Uncorrectable { position };
+        }
+
+        // More errors than the code can handle may look like a single error,
+        // then the "corrected" data is wrong and must not be written back
+        if file
+            .checksum
+            .is_some_and(|checksum| checksum != crc32(&bytes))
+        {
+            return DecodedFile::ChecksumMismatch;
+        }
+        DecodedFile::Decoded {
+            file_type: file.file_type,
+            bytes,
+            corrections,
+        }
+    }
+
+    // Restores the bits found by `decode_file`, which must be called on the same array state.
+    // Also returns whether any disk was changed, by the restored bits, a failover or the
+    // rebuild in progress.
+    pub(crate) fn finish_read(
+        &mut self,
+        name: &str,
+        decoded: DecodedFile,
+    ) -> (FileReadResult, bool) {
+        match decoded {
+            DecodedFile::NotFound => (FileReadResult::NotFound, false),
+            DecodedFile::Uncorrectable { position } => {
+                self.events.record(EventKind::Uncorrectable { position });
+                (FileReadResult::DisksCorrupted, false)
+            }
+            DecodedFile::ChecksumMismatch => {
+                self.events.record(EventKind::SilentCorruption {
+                    name: name.to_owned(),
+                });
+                (FileReadResult::SilentCorruptionDetected, false)
+            }
+            DecodedFile::Decoded {
+                file_type,
+                bytes,
+                corrections,
+            } => {
+                let restored = self.restore_bits(&corrections);
+                self.events.record(EventKind::FileRead {
+                    name: name.to_owned(),
+                    corrected_bits: corrections.len(),
+                });
+                let rebuilt = self.rebuild_in_background();
+                (
+                    FileReadResult::Success(file_type, bytes),
+                    restored || rebuilt,
+                )
             }
-            None => FileReadResult::NotFound,
         }
     }
 
-    fn read_byte(&self, position: usize) -> ReadData<u8> {
-        let mut bits = Vec::with_capacity(self.total_disks);
-        bits.push(self.parity_bit_disk[position]);
+    // Returns whether any disk was changed, by a restored bit or by a spare which took the
+    // place of a suspect disk
+    pub(crate) fn restore_bits(&mut self, corrections: &[(usize, usize)]) -> bool {
+        for (disk_number, bit_number) in corrections {
+            self.restore_bit(*disk_number, *bit_number);
+        }
+        let hot_spares = self.hot_spares;
+        for disk_number in 1..=self.total_disks {
+            if self.disk_statuses[disk_number - 1].state == DiskState::Suspect {
+                self.fail_over(disk_number);
+            }
+        }
+        !corrections.is_empty() || self.hot_spares != hot_spares
+    }
 
-        for i in 0..self.data_bit_disks.len() {
-            bits.push(self.data_bit_disks[i][position]);
+    // Restores invalid bit, there is nothing to restore on a failed disk
+    fn restore_bit(&mut self, disk_number: usize, bit_number: usize) {
+        self.events.record(EventKind::Corrected {
+            disk_number: disk_number + 1,
+            position: bit_number,
+        });
+        // The bits of a disk being rebuilt are expected to be wrong, they do not make it suspect
+        let rebuilding =
+            matches!(&self.rebuild, Some(progress) if progress.disk_number == disk_number + 1);
+        let status = &mut self.disk_statuses[disk_number];
+        if !rebuilding {
+            status.corrected_errors += 1;
+        }
+        if status.state != DiskState::Failed {
+            if status.state != DiskState::Suspect
+                && status.corrected_errors > self.suspect_threshold
+            {
+                status.state = DiskState::Suspect;
+                self.events.record(EventKind::DiskSuspect {
+                    disk_number: disk_number + 1,
+                });
+            }
+            let disk = self.disk_mut(disk_number + 1);
+            disk[bit_number] = !disk[bit_number];
         }
+    }
 
-        for i in 0..self.hamming_bit_disks.len() {
-            bits.push(self.hamming_bit_disks[i][position]);
+    fn read_stripe(&self, stripe: usize) -> ReadData<Vec<u8>> {
+        let bytes_per_stripe = self.bytes_per_stripe();
+        if stripe >= self.disk(1).len() {
+            return ReadData::Valid(vec![0; bytes_per_stripe]);
         }
 
-        match decode(&mut bits) {
-            HammingDecodeResult::NoError { decoded_bits } => {
+        let bits: Vec<Bit> = (1..=self.total_disks)
+            .map(|disk_number| self.disk(disk_number)[stripe])
+            .collect();
+
+        match self.code.decode(&bits) {
+            Err(_) => ReadData::Invalid,
+            Ok(HammingDecodeResult::NoError { decoded_bits }) => {
                 let bytes = bit_vector_to_bytes(&decoded_bits);
-                if bytes.len() == 1 {
-                    ReadData::ValidData(bytes[0])
+                if bytes.len() == bytes_per_stripe {
+                    ReadData::Valid(bytes)
                 } else {
-                    ReadData::InvalidData
+                    ReadData::Invalid
                 }
             }
-            HammingDecodeResult::OneError {
+            Ok(HammingDecodeResult::OneError {
                 decoded_bits,
                 position: invalid_bit,
-            } => {
+            }) => {
                 let bytes = bit_vector_to_bytes(&decoded_bits);
-                if bytes.len() == 1 {
-                    ReadData::CorruptedData {
-                        data: bytes[0],
+                if bytes.len() == bytes_per_stripe {
+                    ReadData::Corrupted {
+                        data: bytes,
                         disk_number: invalid_bit,
-                        bit_number: position,
+                        bit_number: stripe,
                     }
                 } else {
-                    ReadData::InvalidData
+                    ReadData::Invalid
                 }
             }
-            HammingDecodeResult::DoubleError => ReadData::InvalidData,
+            Ok(HammingDecodeResult::DoubleError) => ReadData::Invalid,
         }
     }
 
     pub fn corrupt_disk(&mut self, disk_number: usize) -> bool {
-        let end_data_disk_range = self.data_bit_disks.len() + 1;
-        let end_hamming_disk_range = self.total_disks;
-        if disk_number == 1 {
-            Self::inner_corrupt_disk(&mut self.parity_bit_disk);
-            true
-        } else if 1 < disk_number && disk_number <= end_data_disk_range {
-            Self::inner_corrupt_disk(&mut self.data_bit_disks[disk_number - 1]);
-            true
-        } else if end_data_disk_range < disk_number && disk_number <= end_hamming_disk_range {
-            Self::inner_corrupt_disk(
-                &mut self.hamming_bit_disks[disk_number - end_data_disk_range],
-            );
+        if 0 < disk_number && disk_number <= self.total_disks {
+            Self::inner_corrupt_disk(self.disk_mut(disk_number));
+            self.events.record(EventKind::DiskCorrupted { disk_number });
             true
         } else {
             false
         }
     }
 
-    fn inner_corrupt_disk(disk: &mut Disk) {
-        for i in 0..disk.len() {
-            disk[i] = !disk[i];
+    /// Flips one bit of the disk, as a media error does
+    pub fn flip_bit(&mut self, disk_number: usize, position: usize) -> bool {
+        if 0 < disk_number && disk_number <= self.total_disks && position < self.disk(1).len() {
+            let disk = self.disk_mut(disk_number);
+            disk[position] = !disk[position];
+            self.events.record(EventKind::BitFlipped {
+                disk_number,
+                position,
+            });
+            true
+        } else {
+            false
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::raid::*;
+    pub fn status(&self) -> ArrayStatus {
+        let failed_disks = self
+            .disk_statuses
+            .iter()
+            .filter(|status| status.state == DiskState::Failed)
+            .count();
+        let suspect_disks = self
+            .disk_statuses
+            .iter()
+            .filter(|status| status.state == DiskState::Suspect)
+            .count();
 
-    #[test]
-    fn raid_create_test() {
-        let bytes_per_disk = 1024;
-        let raid_ii = RaidII::from_data_capacity(bytes_per_disk);
-        assert_eq!(raid_ii.disk_size, bytes_per_disk);
-        assert_eq!(raid_ii.files.len(), 0);
-        assert_eq!(raid_ii.data_bit_disks.len(), 8);
-        assert_eq!(raid_ii.hamming_bit_disks.len(), 4);
-        assert_eq!(raid_ii.total_disks, 13);
-        assert_eq!(raid_ii.free_space, raid_ii.disk_size * raid_ii.total_disks);
-        assert_eq!(
-            raid_ii.total_capcity,
-            raid_ii.disk_size * raid_ii.total_disks
-        );
-        assert_eq!(raid_ii.files.len(), 0);
+        let state = if failed_disks > 1 {
+            ArrayState::Failed
+        } else if self.rebuild.is_some() {
+            ArrayState::Rebuilding
+        } else if self.reshape.is_some() {
+            ArrayState::Reshaping
+        } else if failed_disks == 1 || suspect_disks > 0 {
+            ArrayState::Degraded
+        } else {
+            ArrayState::Optimal
+        };
+
+        ArrayStatus {
+            state,
+            disks: self.disk_statuses.clone(),
+            rebuild: self.rebuild.clone(),
+            hot_spares: self.hot_spares,
+            reshape: self.reshape_progress(),
+        }
     }
 
-    #[test]
+    /// Whether files written from now on store a checksum of their content
+    pub fn set_checksums(&mut self, enabled: bool) {
+        self.checksums = enabled;
+    }
+
+    pub fn events(&self) -> &[Event] {
+        self.events.events()
+    }
+
+    /// Writes all following events to the file on the host as JSON lines
+    pub fn set_event_sink(&mut self, path: &Path) -> io::Result<()> {
+        self.events.set_sink(path)
+    }
+
+    /// Returns the error which made the event log stop writing to its sink
+    pub fn take_event_sink_error(&mut self) -> Option<io::Error> {
+        self.events.take_sink_error()
+    }
+
+    /// Timing model of the disk, None if there is no such disk
+    pub fn disk_timing(&self, disk_number: usize) -> Option<DiskTiming> {
+        disk_number
+            .checked_sub(1)
+            .and_then(|index| self.timings.get(index))
+            .copied()
+    }
+
+    pub fn set_disk_timing(&mut self, disk_number: usize, timing: DiskTiming) -> bool {
+        if 0 < disk_number && disk_number <= self.total_disks {
+            self.timings[disk_number - 1] = timing;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Fraction of a rotation the spindle of the disk is ahead of the others, 0 when in sync
+    pub fn spindle_offset(&self, disk_number: usize) -> Option<f64> {
+        disk_number
+            .checked_sub(1)
+            .and_then(|index| self.spindle_offsets.get(index))
+            .copied()
+    }
+
+    /// Turns the spindle of the disk by `offset` of a rotation away from the others
+    pub fn desync_disk(&mut self, disk_number: usize, offset: f64) -> bool {
+        if 0 < disk_number && disk_number <= self.total_disks && offset.is_finite() {
+            self.spindle_offsets[disk_number - 1] = offset.rem_euclid(1.0);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn sync_spindles(&mut self) {
+        self.spindle_offsets
+            .iter_mut()
+            .for_each(|offset| *offset = 0.0);
+    }
+
+    /// Average simulated time of a request for `length` bytes at a random place.
+    /// Every disk stores a bit of every stripe, so every working disk takes part in it,
+    /// and the array waits for the slowest one. With the spindles in sync the data
+    /// comes under all heads at once, otherwise the last one to come sets the time.
+    pub fn access_time(&self, length: usize) -> Duration {
+        let bytes_per_disk = (length.div_ceil(self.bytes_per_stripe()) as u64).div_ceil(8);
+        let working: Vec<usize> = (0..self.total_disks)
+            .filter(|index| self.disk_statuses[*index].state != DiskState::Failed)
+            .collect();
+        if length == 0 || working.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let seek_and_transfer = working
+            .iter()
+            .map(|index| {
+                let timing = &self.timings[*index];
+                timing.seek_time + timing.transfer_time(bytes_per_disk)
+            })
+            .max()
+            .unwrap_or(Duration::ZERO);
+        let rotation = working
+            .iter()
+            .map(|index| self.timings[*index].rotation_time())
+            .max()
+            .unwrap_or(Duration::ZERO);
+
+        // The wait for the latest head over a uniformly random sector is
+        // 1 - sum(gap^2) / 2 of a rotation, where gaps are between the head angles
+        let mut angles: Vec<f64> = working
+            .iter()
+            .map(|index| self.head_angle(*index, self.clock))
+            .collect();
+        angles.sort_by(f64::total_cmp);
+        let wrap_gap = 1.0 - (angles[angles.len() - 1] - angles[0]);
+        let gaps_squares: f64 = angles
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).powi(2))
+            .sum::<f64>()
+            + wrap_gap.powi(2);
+        seek_and_transfer + rotation.mul_f64(1.0 - gaps_squares / 2.0)
+    }
+
+    // Simulated time of the request at the current simulated time: every disk seeks,
+    // then waits until the first sector of the request comes under its head
+    fn request_time(&self, start: usize, length: usize) -> Duration {
+        if length == 0 {
+            return Duration::ZERO;
+        }
+        let bytes_per_stripe = self.bytes_per_stripe();
+        let first_stripe = start / bytes_per_stripe;
+        let stripes = (start + length).div_ceil(bytes_per_stripe) - first_stripe;
+        let bytes_per_disk = (stripes as u64).div_ceil(8);
+        let sector_angle = timing::sector_angle(first_stripe as u64 / 8);
+
+        (0..self.total_disks)
+            .filter(|index| self.disk_statuses[*index].state != DiskState::Failed)
+            .map(|index| {
+                let timing = &self.timings[index];
+                let head_angle = self.head_angle(index, self.clock + timing.seek_time);
+                let wait = (sector_angle - head_angle).rem_euclid(1.0);
+                timing.seek_time
+                    + timing.rotation_time().mul_f64(wait)
+                    + timing.transfer_time(bytes_per_disk)
+            })
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    // Fraction of the rotation of the disk at the simulated time
+    fn head_angle(&self, index: usize, time: Duration) -> f64 {
+        let rotations = time.as_secs_f64() / self.timings[index].rotation_time().as_secs_f64();
+        (self.spindle_offsets[index] + rotations).rem_euclid(1.0)
+    }
+
+    /// Simulated time spent by the timed operations so far
+    pub fn simulated_time(&self) -> Duration {
+        self.clock
+    }
+
+    /// `write_file` which also returns its simulated time
+    pub fn write_file_timed(
+        &mut self,
+        data: &[u8],
+        file_type: FileType,
+        name: &str,
+    ) -> (FileWriteResult, Duration) {
+        let start = self.total_capcity - self.free_space;
+        let result = self.write_file(data, file_type, name);
+        let elapsed = match result {
+            FileWriteResult::Success => self.request_time(start, data.len()),
+            FileWriteResult::NotEnoughSpace => Duration::ZERO,
+        };
+        self.clock += elapsed;
+        (result, elapsed)
+    }
+
+    /// `read_file` which also returns its simulated time.
+    /// Corrected bits are written back in the same rotation, it takes no extra time.
+    pub fn read_file_timed(&mut self, name: &str) -> (FileReadResult, Duration) {
+        let elapsed = match self.files.iter().find(|file| file.name == name) {
+            Some(file) => self.request_time(file.start_pos, file.size),
+            None => Duration::ZERO,
+        };
+        self.clock += elapsed;
+        (self.read_file(name), elapsed)
+    }
+
+    /// Disks with more corrected errors than the threshold are marked as suspect
+    pub fn set_suspect_threshold(&mut self, threshold: usize) {
+        self.suspect_threshold = threshold;
+    }
+
+    /// The disk stops working and all its bits are lost
+    pub fn fail_disk(&mut self, disk_number: usize) -> bool {
+        if 0 < disk_number && disk_number <= self.total_disks {
+            let disk = self.disk_mut(disk_number);
+            disk.fill(false);
+            self.disk_statuses[disk_number - 1].state = DiskState::Failed;
+            self.events.record(EventKind::DiskFailed { disk_number });
+            if let Some(rebuild) = &self.rebuild {
+                if rebuild.disk_number == disk_number {
+                    self.rebuild = None;
+                }
+            }
+            self.fail_over(disk_number);
+            if self.rebuild.is_none() {
+                self.start_queued_failover();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Swaps a failed or suspect disk for an empty one and starts restoring its data from
+    /// the other disks. The restoring is done by `rebuild_step` or `rebuild`.
+    pub fn replace_disk(&mut self, disk_number: usize) -> bool {
+        if disk_number == 0 || disk_number > self.total_disks || self.rebuild.is_some() {
+            return false;
+        }
+        // Wiping a healthy disk would only leave the array without its redundancy
+        if !matches!(
+            self.disk_statuses[disk_number - 1].state,
+            DiskState::Failed | DiskState::Suspect
+        ) {
+            return false;
+        }
+
+        let disk = self.disk_mut(disk_number);
+        disk.fill(false);
+        self.disk_statuses[disk_number - 1] = DiskStatus {
+            state: DiskState::Replaced,
+            corrected_errors: 0,
+        };
+        self.events.record(EventKind::DiskReplaced { disk_number });
+        self.rebuild = Some(RebuildProgress {
+            disk_number,
+            rebuilt_positions: 0,
+            total_positions: self.disk(1).len(),
+            lost_positions: 0,
+        });
+        true
+    }
+
+    /// Restores at most `positions` bits of the replaced disk.
+    /// Returns the progress, or None if there is no rebuild in progress.
+    pub fn rebuild_step(&mut self, positions: usize) -> Option<RebuildProgress> {
+        let mut progress = self.rebuild.take()?;
+        let end = progress
+            .total_positions
+            .min(progress.rebuilt_positions + positions);
+
+        for position in progress.rebuilt_positions..end {
+            match self.read_stripe(position) {
+                ReadData::Valid(_) => (),
+                ReadData::Corrupted { disk_number, .. } => {
+                    // Errors on other disks are left to be fixed by reads
+                    if disk_number + 1 == progress.disk_number {
+                        let disk = self.disk_mut(progress.disk_number);
+                        disk[position] = !disk[position];
+                    }
+                }
+                ReadData::Invalid => progress.lost_positions += 1,
+            }
+        }
+        progress.rebuilt_positions = end;
+
+        if progress.rebuilt_positions < progress.total_positions {
+            self.rebuild = Some(progress.clone());
+        } else {
+            self.events.record(EventKind::RebuildFinished {
+                disk_number: progress.disk_number,
+                lost_positions: progress.lost_positions,
+            });
+            self.start_queued_failover();
+        }
+        Some(progress)
+    }
+
+    /// Sets the number of spare disks. A disk which fails or becomes suspect is replaced
+    /// by a spare right away, and rebuilt a little with every following file read or write.
+    pub fn set_hot_spares(&mut self, count: usize) {
+        self.hot_spares = count;
+        for disk_number in 1..=self.total_disks {
+            if matches!(
+                self.disk_statuses[disk_number - 1].state,
+                DiskState::Failed | DiskState::Suspect
+            ) {
+                self.fail_over(disk_number);
+            }
+        }
+    }
+
+    pub fn hot_spares(&self) -> usize {
+        self.hot_spares
+    }
+
+    // Swaps the disk for a spare, or queues it if another disk is being rebuilt
+    fn fail_over(&mut self, disk_number: usize) {
+        if self.hot_spares == 0 || self.failover_queue.contains(&disk_number) {
+            return;
+        }
+        if let Some(progress) = &self.rebuild {
+            if progress.disk_number != disk_number {
+                self.failover_queue.push(disk_number);
+            }
+            return;
+        }
+
+        self.hot_spares -= 1;
+        self.events
+            .record(EventKind::SpareActivated { disk_number });
+        self.replace_disk(disk_number);
+    }
+
+    fn start_queued_failover(&mut self) {
+        while self.rebuild.is_none() && !self.failover_queue.is_empty() {
+            let disk_number = self.failover_queue.remove(0);
+            // The disk may have been replaced by the user meanwhile
+            if matches!(
+                self.disk_statuses[disk_number - 1].state,
+                DiskState::Failed | DiskState::Suspect
+            ) {
+                self.fail_over(disk_number);
+            }
+        }
+    }
+
+    // Returns whether a rebuild was in progress, so the replaced disk may have changed
+    fn rebuild_in_background(&mut self) -> bool {
+        self.rebuild_step(BACKGROUND_REBUILD_POSITIONS).is_some()
+    }
+
+    /// Saves the disks, the file table and the health of the disks, returns the id to give
+    /// to `restore`. Only the stripes written after the snapshot take more memory.
+    pub fn snapshot(&mut self) -> usize {
+        let id = self.next_snapshot_id;
+        self.next_snapshot_id += 1;
+        let snapshot = self.save_state();
+        self.snapshots.insert(id, snapshot);
+        self.events.record(EventKind::SnapshotTaken { id });
+        id
+    }
+
+    /// Rolls the array back to the snapshot, which is kept to be restored again.
+    /// The event log, disk timings and settings are not part of a snapshot.
+    pub fn restore(&mut self, id: usize) -> bool {
+        let Some(snapshot) = self.snapshots.remove(&id) else {
+            return false;
+        };
+        self.load_state(&snapshot);
+        self.snapshots.insert(id, snapshot);
+        self.events.record(EventKind::SnapshotRestored { id });
+        true
+    }
+
+    pub fn delete_snapshot(&mut self, id: usize) -> bool {
+        self.snapshots.remove(&id).is_some()
+    }
+
+    pub fn snapshot_ids(&self) -> Vec<usize> {
+        self.snapshots.keys().copied().collect()
+    }
+
+    /// Runs an operation which can be undone and returns what `run` returned, it is journaled
+    /// only if that succeeded. A new operation can not be redone after, the undone ones are
+    /// forgotten.
+    pub fn journaled<T: Succeeded>(
+        &mut self,
+        operation: &str,
+        run: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let before = self.save_state();
+        let result = run(self);
+        if !result.succeeded() {
+            return result;
+        }
+        self.undo_journal.push((operation.to_owned(), before));
+        if self.undo_journal.len() > JOURNAL_LENGTH {
+            self.undo_journal.remove(0);
+        }
+        self.redo_journal.clear();
+        result
+    }
+
+    /// Goes back to the state before the last journaled operation, returns its name
+    pub fn undo(&mut self) -> Option<String> {
+        let (operation, state) = self.undo_journal.pop()?;
+        self.redo_journal
+            .push((operation.clone(), self.save_state()));
+        self.load_state(&state);
+        self.events.record(EventKind::Undone {
+            operation: operation.clone(),
+        });
+        Some(operation)
+    }
+
+    /// Applies again the last undone operation, returns its name
+    pub fn redo(&mut self) -> Option<String> {
+        let (operation, state) = self.redo_journal.pop()?;
+        self.undo_journal
+            .push((operation.clone(), self.save_state()));
+        self.load_state(&state);
+        self.events.record(EventKind::Redone {
+            operation: operation.clone(),
+        });
+        Some(operation)
+    }
+
+    // Names of the operations which can be undone, the last one first
+    pub fn undo_operations(&self) -> impl Iterator<Item = &str> {
+        self.undo_journal
+            .iter()
+            .rev()
+            .map(|(operation, _)| operation.as_str())
+    }
+
+    fn save_state(&self) -> Snapshot {
+        Snapshot {
+            parity_bit_disk: self.parity_bit_disk.clone(),
+            data_bit_disks: self.data_bit_disks.clone(),
+            hamming_bit_disks: self.hamming_bit_disks.clone(),
+            free_space: self.free_space,
+            written_blocks: self.written_blocks.clone(),
+            files: self.files.clone(),
+            disk_statuses: self.disk_statuses.clone(),
+            rebuild: self.rebuild.clone(),
+            hot_spares: self.hot_spares,
+            failover_queue: self.failover_queue.clone(),
+        }
+    }
+
+    fn load_state(&mut self, snapshot: &Snapshot) {
+        self.parity_bit_disk = snapshot.parity_bit_disk.clone();
+        self.data_bit_disks = snapshot.data_bit_disks.clone();
+        self.hamming_bit_disks = snapshot.hamming_bit_disks.clone();
+        self.free_space = snapshot.free_space;
+        self.written_blocks = snapshot.written_blocks.clone();
+        self.files = snapshot.files.clone();
+        self.disk_statuses = snapshot.disk_statuses.clone();
+        self.rebuild = snapshot.rebuild.clone();
+        self.hot_spares = snapshot.hot_spares;
+        self.failover_queue = snapshot.failover_queue.clone();
+        self.rewind_reshape(0);
+    }
+
+    /// Starts copying every stripe to an array with `data_disks` data disks and as many
+    /// Hamming disks as its code needs. The copying is done by `reshape_step` or `reshape`,
+    /// the array keeps working with the old disks until it is finished.
+    /// Starting the reshape which is already in progress only returns its progress.
+    pub fn start_reshape(&mut self, data_disks: usize) -> Result<ReshapeProgress, GeometryError> {
+        let code = Self::stripe_code(data_disks, self.code.extended())?;
+        if let Some(reshape) = &self.reshape {
+            return if reshape.code == code {
+                Ok(reshape.progress.clone())
+            } else {
+                Err(GeometryError::Busy)
+            };
+        }
+        if self.rebuild.is_some() {
+            return Err(GeometryError::Busy);
+        }
+
+        let capacity = self.disk_size * code.codeword_len();
+        let needed = self.data_end();
+        if needed > capacity {
+            return Err(GeometryError::NotEnoughSpace { needed, capacity });
+        }
+
+        let progress = ReshapeProgress {
+            from_data_disks: self.code.data_bits(),
+            to_data_disks: data_disks,
+            reshaped_bytes: 0,
+            total_bytes: self.stored_bytes().min(capacity),
+        };
+        self.reshape = Some(Reshape {
+            code,
+            disks: vec![Disk::new(); code.codeword_len()],
+            capacity,
+            progress: progress.clone(),
+        });
+        self.events.record(EventKind::ReshapeStarted {
+            from_data_disks: progress.from_data_disks,
+            to_data_disks: data_disks,
+        });
+        Ok(progress)
+    }
+
+    /// Copies at least `bytes` bytes, rounded up to whole new stripes, to the new disks.
+    /// Once everything is copied the array switches to them, the snapshots and the undo
+    /// journal are dropped, because they belong to the old disks.
+    /// Returns the progress, or None if there is no reshape in progress. Fails and keeps the
+    /// reshape in progress if the files and blocks do not fit the new geometry any more, which
+    /// happens when an undo or a snapshot brings back more of them, or if a stripe of a file
+    /// or a block can not be decoded.
+    pub fn reshape_step(&mut self, bytes: usize) -> Result<Option<ReshapeProgress>, GeometryError> {
+        let Some(mut reshape) = self.reshape.take() else {
+            return Ok(None);
+        };
+        let needed = self.data_end();
+        if needed > reshape.capacity {
+            let capacity = reshape.capacity;
+            self.reshape = Some(reshape);
+            return Err(GeometryError::NotEnoughSpace { needed, capacity });
+        }
+        let old_bytes_per_stripe = self.bytes_per_stripe();
+        let new_bytes_per_stripe = reshape.code.data_bits() / 8;
+        // Files written since the start grow the stored part of the address space
+        let progress = &mut reshape.progress;
+        progress.total_bytes = self.stored_bytes().min(reshape.capacity);
+        let start = progress.reshaped_bytes;
+        let end = progress
+            .total_bytes
+            .min(start.saturating_add(bytes.max(1)))
+            .next_multiple_of(new_bytes_per_stripe)
+            .max(start);
+
+        let mut data = Vec::with_capacity(end - start);
+        while start + data.len() < end {
+            let position = start + data.len();
+            let decoded = self.decode_range(position, end - position);
+            data.extend_from_slice(&decoded.bytes);
+            if let Some(invalid_position) = decoded.invalid_position {
+                if self.holds_data(invalid_position) {
+                    self.reshape = Some(reshape);
+                    return Err(GeometryError::Uncorrectable {
+                        position: invalid_position,
+                    });
+                }
+                // Nothing is stored there, the stripe becomes zeros
+                let next_stripe =
+                    (invalid_position / old_bytes_per_stripe + 1) * old_bytes_per_stripe;
+                data.resize(next_stripe.min(end) - start, 0);
+            }
+        }
+
+        let stripes = end / new_bytes_per_stripe;
+        for disk in &mut reshape.disks {
+            if disk.len() < stripes {
+                disk.resize(stripes, false);
+            }
+        }
+        for (index, bytes) in data.chunks(new_bytes_per_stripe).enumerate() {
+            let bits = match reshape.code.encode(&bit_vector_from_bytes(bytes)) {
+                Ok(bits) => bits,
+                Err(error) => unreachable!("the new code encodes whole stripes: {}", error),
+            };
+            for (disk, bit) in reshape.disks.iter_mut().zip(bits) {
+                disk[start / new_bytes_per_stripe + index] = bit;
+            }
+        }
+        progress.reshaped_bytes = end;
+
+        let progress = progress.clone();
+        if progress.reshaped_bytes < progress.total_bytes {
+            self.reshape = Some(reshape);
+        } else {
+            self.finish_reshape(reshape);
+        }
+        Ok(Some(progress))
+    }
+
+    /// Reshapes the array to `data_disks` data disks at once, or finishes the reshape
+    /// to them which is in progress
+    pub fn reshape(&mut self, data_disks: usize) -> Result<ReshapeProgress, GeometryError> {
+        let mut progress = self.start_reshape(data_disks)?;
+        while let Some(step) = self.reshape_step(usize::MAX)? {
+            progress = step;
+        }
+        Ok(progress)
+    }
+
+    pub fn reshape_progress(&self) -> Option<ReshapeProgress> {
+        self.reshape
+            .as_ref()
+            .map(|reshape| reshape.progress.clone())
+    }
+
+    fn finish_reshape(&mut self, reshape: Reshape) {
+        let used = self.total_capcity - self.free_space;
+        let mut disks = reshape.disks.into_iter();
+        if self.code.extended() {
+            self.parity_bit_disk = disks.next();
+        }
+        self.data_bit_disks = disks.by_ref().take(reshape.code.data_bits()).collect();
+        self.hamming_bit_disks = disks.collect();
+        self.code = reshape.code;
+
+        self.total_disks = self.code.codeword_len();
+        self.total_capcity = reshape.capacity;
+        self.free_space = reshape.capacity - used;
+        // The new disks are all online, the errors of the old ones are decoded away
+        self.disk_statuses = vec![
+            DiskStatus {
+                state: DiskState::Online,
+                corrected_errors: 0,
+            };
+            self.total_disks
+        ];
+        self.rebuild = None;
+        self.failover_queue.clear();
+        let timing = self.timings[0];
+        self.timings = vec![timing; self.total_disks];
+        self.spindle_offsets = vec![0.0; self.total_disks];
+        self.snapshots.clear();
+        self.undo_journal.clear();
+        self.redo_journal.clear();
+        self.events.record(EventKind::ReshapeFinished {
+            data_disks: self.code.data_bits(),
+        });
+    }
+
+    // Stripes from `position` on have to be copied again after they were written
+    fn rewind_reshape(&mut self, position: usize) {
+        if let Some(reshape) = &mut self.reshape {
+            let bytes_per_stripe = reshape.code.data_bits() / 8;
+            let aligned = position / bytes_per_stripe * bytes_per_stripe;
+            let progress = &mut reshape.progress;
+            progress.reshaped_bytes = progress.reshaped_bytes.min(aligned);
+        }
+    }
+
+    // Bytes of the address space which have been written to the disks
+    fn stored_bytes(&self) -> usize {
+        self.disk(1).len() * self.bytes_per_stripe()
+    }
+
+    /// Finishes the rebuild in progress
+    pub fn rebuild(&mut self) -> Option<RebuildProgress> {
+        let progress = self.rebuild.as_ref()?;
+        let remaining = progress.total_positions - progress.rebuilt_positions;
+        self.rebuild_step(remaining)
+    }
+
+    // Disk numbers start from 1, the disk `n` stores the bit `n - 1` of every codeword
+    fn disk(&self, disk_number: usize) -> &Disk {
+        let mut index = disk_number - 1;
+        if let Some(parity_bit_disk) = &self.parity_bit_disk {
+            if index == 0 {
+                return parity_bit_disk;
+            }
+            index -= 1;
+        }
+
+        if index < self.data_bit_disks.len() {
+            &self.data_bit_disks[index]
+        } else {
+            &self.hamming_bit_disks[index - self.data_bit_disks.len()]
+        }
+    }
+
+    fn disk_mut(&mut self, disk_number: usize) -> &mut Disk {
+        let mut index = disk_number - 1;
+        if let Some(parity_bit_disk) = &mut self.parity_bit_disk {
+            if index == 0 {
+                return parity_bit_disk;
+            }
+            index -= 1;
+        }
+
+        if index < self.data_bit_disks.len() {
+            &mut self.data_bit_disks[index]
+        } else {
+            &mut self.hamming_bit_disks[index - self.data_bit_disks.len()]
+        }
+    }
+
+    fn inner_corrupt_disk(disk: &mut Disk) {
+        disk.invert();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raid::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn raid_create_test() {
+        let bytes_per_disk = 1024;
+        let raid_ii = RaidII::from_data_capacity(bytes_per_disk);
+        assert_eq!(raid_ii.disk_size, bytes_per_disk);
+        assert_eq!(raid_ii.files.len(), 0);
+        assert_eq!(raid_ii.data_bit_disks.len(), 8);
+        assert_eq!(raid_ii.hamming_bit_disks.len(), 4);
+        assert_eq!(raid_ii.total_disks, 13);
+        assert_eq!(raid_ii.free_space, raid_ii.disk_size * raid_ii.total_disks);
+        assert_eq!(
+            raid_ii.total_capcity,
+            raid_ii.disk_size * raid_ii.total_disks
+        );
+        assert_eq!(raid_ii.files.len(), 0);
+    }
+
+    #[test]
     fn raid_use_test1() {
         let bytes_per_disk = 1024;
         let mut raid_ii = RaidII::from_data_capacity(bytes_per_disk);
@@ -279,13 +1772,14 @@ mod tests {
 
         match raid_ii.write_file(&bytes, file_type, &file_name) {
             FileWriteResult::Success => match raid_ii.read_file(&file_name) {
-                FileReadResult::NotFound => assert!(false),
-                FileReadResult::DisksCorrupted => assert!(false),
+                FileReadResult::NotFound => unreachable!(),
+                FileReadResult::DisksCorrupted => unreachable!(),
+                FileReadResult::SilentCorruptionDetected => unreachable!(),
                 FileReadResult::Success(find_file_type, find_bytes) => match find_file_type {
                     FileType::Text => assert_eq!(bytes, find_bytes),
                 },
             },
-            FileWriteResult::NotEnoughSpace => assert!(false),
+            FileWriteResult::NotEnoughSpace => unreachable!(),
         }
     }
 
@@ -304,16 +1798,80 @@ mod tests {
 
         match raid_ii.write_file(&bytes, file_type, &file_name) {
             FileWriteResult::Success => match raid_ii.read_file(&file_name) {
-                FileReadResult::NotFound => assert!(false),
-                FileReadResult::DisksCorrupted => assert!(false),
+                FileReadResult::NotFound => unreachable!(),
+                FileReadResult::DisksCorrupted => unreachable!(),
+                FileReadResult::SilentCorruptionDetected => unreachable!(),
                 FileReadResult::Success(find_file_type, find_bytes) => match find_file_type {
                     FileType::Text => assert_eq!(bytes, find_bytes),
                 },
             },
-            FileWriteResult::NotEnoughSpace => assert!(false),
+            FileWriteResult::NotEnoughSpace => unreachable!(),
         }
     }
 
+    #[test]
+    fn restore_test() {
+        // The corrected bit is written back, so a second damaged disk is still corrected
+        for disk_number in 2..=8 {
+            let mut raid_ii = RaidII::from_data_capacity(1024);
+            let bytes = "Hello, Rust!".as_bytes().to_vec();
+            let file_name = "Greeting".to_owned();
+            raid_ii.write_file(&bytes, FileType::Text, &file_name);
+            raid_ii.corrupt_disk(disk_number);
+            raid_ii.read_file(&file_name);
+            raid_ii.corrupt_disk(1);
+
+            match raid_ii.read_file(&file_name) {
+                FileReadResult::Success(_, find_bytes) => assert_eq!(bytes, find_bytes),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn files_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello", FileType::Text, "first");
+        raid_ii.write_file(b"Rust!", FileType::Text, "second");
+
+        let files: Vec<&File> = raid_ii.files().collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].name(), "second");
+        assert_eq!(files[1].size(), 5);
+        assert_eq!(files[1].start_pos(), 5);
+        assert_eq!(files[1].end_pos(), 10);
+        assert_eq!(files[1].file_type(), FileType::Text);
+        assert_eq!(raid_ii.free_space(), raid_ii.total_capcity - 10);
+    }
+
+    #[test]
+    fn file_health_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello", FileType::Text, "greeting");
+        assert!(raid_ii.file_health("missing").is_none());
+        assert_eq!(
+            raid_ii.file_health("greeting"),
+            Some(FileHealth {
+                clean: 5,
+                correctable: 0,
+                uncorrectable: 0
+            })
+        );
+
+        raid_ii.data_bit_disks[3][1] = !raid_ii.data_bit_disks[3][1];
+        raid_ii.disk_mut(1)[2] = !raid_ii.disk_mut(1)[2];
+        raid_ii.hamming_bit_disks[0][2] = !raid_ii.hamming_bit_disks[0][2];
+        raid_ii.hamming_bit_disks[1][4] = !raid_ii.hamming_bit_disks[1][4];
+        assert_eq!(
+            raid_ii.file_health("greeting"),
+            Some(FileHealth {
+                clean: 2,
+                correctable: 2,
+                uncorrectable: 1
+            })
+        );
+    }
+
     #[test]
     fn corrupt_disk_test1() {
         let bytes_per_disk = 1024;
@@ -327,10 +1885,920 @@ mod tests {
         raid_ii.corrupt_disk(1);
         raid_ii.corrupt_disk(2);
 
-        match raid_ii.read_file(&file_name) {
-            FileReadResult::NotFound => assert!(false),
-            FileReadResult::DisksCorrupted => assert!(true),
-            FileReadResult::Success(..) => assert!(false),
+        assert!(matches!(
+            raid_ii.read_file(&file_name),
+            FileReadResult::DisksCorrupted
+        ));
+    }
+
+    #[test]
+    fn corrupt_disk_numbers_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hi", FileType::Text, "greeting");
+        let before: Vec<Bit> = (0..13).map(|n| raid_ii.disk_mut(n + 1)[0]).collect();
+        // Disk 9 used to index `data_bit_disks[8]`, one past the last data disk, and panic
+        assert!(raid_ii.corrupt_disk(9));
+        assert_eq!(raid_ii.disk_mut(9)[0], !before[8]);
+        raid_ii.corrupt_disk(9);
+        for disk_number in 1..=13 {
+            assert!(raid_ii.corrupt_disk(disk_number));
+            for n in 1..=13 {
+                let flipped = raid_ii.disk_mut(n)[0] != before[n - 1];
+                assert_eq!(flipped, n == disk_number);
+            }
+            raid_ii.corrupt_disk(disk_number);
+        }
+        assert!(!raid_ii.corrupt_disk(0));
+        assert!(!raid_ii.corrupt_disk(14));
+    }
+
+    #[test]
+    fn status_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let bytes = b"Hello, Rust!".to_vec();
+        raid_ii.write_file(&bytes, FileType::Text, "greeting");
+        let status = raid_ii.status();
+        assert_eq!(status.state, ArrayState::Optimal);
+        assert_eq!(status.disks.len(), 13);
+        assert!(status.rebuild.is_none());
+
+        raid_ii.set_suspect_threshold(20);
+        raid_ii.corrupt_disk(5);
+        raid_ii.read_file("greeting");
+        raid_ii.corrupt_disk(5);
+        raid_ii.read_file("greeting");
+        let status = raid_ii.status();
+        assert_eq!(status.disks[4].corrected_errors, 24);
+        assert_eq!(status.disks[4].state, DiskState::Suspect);
+        assert_eq!(status.disks[3].corrected_errors, 0);
+        assert_eq!(status.disks[3].state, DiskState::Online);
+        assert_eq!(status.state, ArrayState::Degraded);
+    }
+
+    #[test]
+    fn fail_and_replace_disk_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let bytes = b"Hello, Rust!".to_vec();
+        raid_ii.write_file(&bytes, FileType::Text, "greeting");
+        let original_disk = raid_ii.disk_mut(7).clone();
+
+        assert!(raid_ii.fail_disk(7));
+        assert_eq!(raid_ii.status().state, ArrayState::Degraded);
+        assert_eq!(raid_ii.status().disks[6].state, DiskState::Failed);
+        match raid_ii.read_file("greeting") {
+            FileReadResult::Success(_, find_bytes) => assert_eq!(bytes, find_bytes),
+            _ => unreachable!(),
+        }
+
+        assert!(raid_ii.replace_disk(7));
+        assert!(!raid_ii.replace_disk(8));
+        let progress = raid_ii.rebuild_step(5).unwrap();
+        assert_eq!(progress.rebuilt_positions, 5);
+        assert_eq!(progress.total_positions, 12);
+        assert_eq!(raid_ii.status().state, ArrayState::Rebuilding);
+
+        let progress = raid_ii.rebuild().unwrap();
+        assert_eq!(progress.rebuilt_positions, 12);
+        assert_eq!(progress.lost_positions, 0);
+        assert!(raid_ii.rebuild().is_none());
+        assert_eq!(*raid_ii.disk_mut(7), original_disk);
+        let status = raid_ii.status();
+        assert_eq!(status.state, ArrayState::Optimal);
+        assert_eq!(status.disks[6].state, DiskState::Replaced);
+        assert!(!raid_ii.replace_disk(7));
+        assert!(!raid_ii.replace_disk(8));
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(&[7; 300], FileType::Text, "first");
+        let id = raid_ii.snapshot();
+        raid_ii.corrupt_disk(3);
+        raid_ii.corrupt_disk(5);
+        raid_ii.fail_disk(9);
+        assert_eq!(raid_ii.read_file("first"), FileReadResult::DisksCorrupted);
+        assert!(raid_ii.restore(id));
+        assert_eq!(
+            raid_ii.read_file("first"),
+            FileReadResult::Success(FileType::Text, vec![7; 300])
+        );
+        assert_eq!(raid_ii.status().state, ArrayState::Optimal);
+
+        // Files written after the snapshot are gone, the snapshot can be restored again
+        raid_ii.write_file(b"later", FileType::Text, "second");
+        let free_space = raid_ii.free_space();
+        assert!(raid_ii.restore(id));
+        assert_eq!(raid_ii.read_file("second"), FileReadResult::NotFound);
+        assert!(raid_ii.free_space() > free_space);
+        assert!(raid_ii.restore(id));
+
+        assert!(!raid_ii.restore(id + 1));
+        assert_eq!(raid_ii.snapshot_ids(), vec![id]);
+        assert!(raid_ii.delete_snapshot(id));
+        assert!(!raid_ii.restore(id));
+        assert!(matches!(
+            raid_ii.events().last().unwrap().kind,
+            EventKind::SnapshotRestored { .. }
+        ));
+    }
+
+    #[test]
+    fn snapshot_copy_on_write_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(&[1; 1000], FileType::Text, "data");
+        let id = raid_ii.snapshot();
+        let chunks = 1000usize.div_ceil(crate::disk::CHUNK_STRIPES);
+        let shared = |raid_ii: &RaidII| -> usize {
+            let snapshot = &raid_ii.snapshots[&id];
+            (0..8)
+                .map(|i| raid_ii.data_bit_disks[i].shared_chunks(&snapshot.data_bit_disks[i]))
+                .sum()
+        };
+        assert_eq!(shared(&raid_ii), 8 * chunks);
+
+        // Only the chunk of the changed stripe is copied
+        raid_ii.write_bytes(500, &[2]);
+        assert_eq!(shared(&raid_ii), 8 * (chunks - 1));
+        assert!(raid_ii.restore(id));
+        assert_eq!(shared(&raid_ii), 8 * chunks);
+    }
+
+    #[test]
+    fn delete_file_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let free_space = raid_ii.free_space();
+        raid_ii.write_file(b"first", FileType::Text, "first");
+        raid_ii.write_file(b"second file", FileType::Text, "second");
+        raid_ii.write_file(b"third", FileType::Text, "third");
+        // Errors move with their stripes and are still corrected
+        raid_ii.corrupt_disk(6);
+
+        assert_eq!(raid_ii.delete_file("second"), Ok(()));
+        assert_eq!(raid_ii.delete_file("second"), Err(DeleteError::NotFound));
+        assert_eq!(raid_ii.read_file("second"), FileReadResult::NotFound);
+        assert_eq!(raid_ii.free_space(), free_space - 10);
+        let third = raid_ii.files().find(|file| file.name() == "third").unwrap();
+        assert_eq!((third.start_pos(), third.end_pos()), (5, 10));
+        assert_eq!(
+            raid_ii.read_file("third"),
+            FileReadResult::Success(FileType::Text, b"third".to_vec())
+        );
+        assert_eq!(
+            raid_ii.read_file("first"),
+            FileReadResult::Success(FileType::Text, b"first".to_vec())
+        );
+        assert_eq!(raid_ii.decode_range(10, 11).bytes, vec![0; 11]);
+
+        raid_ii.write_file(b"fourth", FileType::Text, "fourth");
+        assert_eq!(raid_ii.files().last().unwrap().start_pos(), 10);
+
+        // Blocks can use the space of deleted files
+        let mut block = vec![0; DEFAULT_BLOCK_SIZE];
+        assert_eq!(
+            raid_ii.read_block(0, &mut block),
+            Err(BlockError::FileSpace { lba: 0 })
+        );
+        for name in ["first", "third", "fourth"] {
+            assert_eq!(raid_ii.delete_file(name), Ok(()));
+        }
+        raid_ii.read_block(0, &mut block).unwrap();
+    }
+
+    #[test]
+    fn delete_before_damaged_stripe_test() {
+        let mut raid_ii = RaidII::from_data_capacity(64);
+        raid_ii.reshape(16).unwrap();
+        raid_ii.write_file(b"abc", FileType::Text, "first");
+        raid_ii.write_file(b"defg", FileType::Text, "second");
+        raid_ii.write_file(b"hijk", FileType::Text, "third");
+        // Bytes 4 and 5 of the second file can not be decoded
+        raid_ii.flip_bit(3, 2);
+        raid_ii.flip_bit(4, 2);
+
+        // Moving the later files would need that stripe, nothing is changed
+        assert_eq!(
+            raid_ii.delete_file("first"),
+            Err(DeleteError::Uncorrectable { position: 4 })
+        );
+        assert_eq!(raid_ii.files().count(), 3);
+        assert_eq!(raid_ii.read_file("second"), FileReadResult::DisksCorrupted);
+        assert_eq!(
+            raid_ii.read_file("third"),
+            FileReadResult::Success(FileType::Text, b"hijk".to_vec())
+        );
+
+        // The damaged file itself can go, the files after it move with it
+        assert_eq!(raid_ii.delete_file("second"), Ok(()));
+        assert_eq!(raid_ii.delete_file("first"), Ok(()));
+        assert_eq!(
+            raid_ii.read_file("third"),
+            FileReadResult::Success(FileType::Text, b"hijk".to_vec())
+        );
+    }
+
+    #[test]
+    fn wide_stripes_test() {
+        let mut raid_ii = RaidII::with_data_disks(1024, 16, true).unwrap();
+        assert_eq!(raid_ii.total_disks(), 22);
+        assert_eq!(raid_ii.hamming_bit_disks.len(), 5);
+        // Odd sizes leave files sharing a stripe
+        raid_ii.write_file(b"first", FileType::Text, "first");
+        raid_ii.write_file(b"second file", FileType::Text, "second");
+        raid_ii.write_file(b"third", FileType::Text, "third");
+        assert_eq!(raid_ii.disk(1).len(), 11);
+        assert_eq!(
+            raid_ii.file_health("second"),
+            Some(FileHealth {
+                clean: 6,
+                correctable: 0,
+                uncorrectable: 0
+            })
+        );
+
+        raid_ii.corrupt_disk(9);
+        assert_eq!(raid_ii.delete_file("second"), Ok(()));
+        assert_eq!(read_text(&mut raid_ii, "first"), Some(b"first".to_vec()));
+        assert_eq!(read_text(&mut raid_ii, "third"), Some(b"third".to_vec()));
+        assert_eq!(raid_ii.decode_range(10, 11).bytes, vec![0; 11]);
+        assert_eq!(
+            RaidII::with_data_disks(1024, 12, true).err(),
+            Some(GeometryError::NotWholeBytes { data_disks: 12 })
+        );
+    }
+
+    #[test]
+    fn reshape_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"survives the reshape", FileType::Text, "first");
+        raid_ii.write_file(b"so does this one", FileType::Text, "second");
+        raid_ii.snapshot();
+        raid_ii.corrupt_disk(4);
+
+        let progress = raid_ii.reshape(16).unwrap();
+        assert_eq!(progress.reshaped_bytes, 36);
+        assert_eq!(raid_ii.total_disks(), 22);
+        assert_eq!(raid_ii.code().data_bits(), 16);
+        assert_eq!(raid_ii.free_space(), 1024 * 22 - 36);
+        assert_eq!(raid_ii.status().state, ArrayState::Optimal);
+        assert_eq!(raid_ii.status().disks.len(), 22);
+        assert!(raid_ii.snapshot_ids().is_empty());
+        assert!(matches!(
+            raid_ii.events().last().unwrap().kind,
+            EventKind::ReshapeFinished { data_disks: 16 }
+        ));
+
+        // Errors are corrected with the new code
+        raid_ii.corrupt_disk(20);
+        assert_eq!(
+            read_text(&mut raid_ii, "first"),
+            Some(b"survives the reshape".to_vec())
+        );
+        assert_eq!(raid_ii.status().disks[19].corrected_errors, 10);
+
+        raid_ii.reshape(8).unwrap();
+        assert_eq!(raid_ii.total_disks(), 13);
+        assert_eq!(
+            read_text(&mut raid_ii, "second"),
+            Some(b"so does this one".to_vec())
+        );
+    }
+
+    #[test]
+    fn reshape_step_test() {
+        let mut raid_ii = RaidII::from_data_capacity(64);
+        raid_ii.write_file(&[7; 100], FileType::Text, "first");
+        raid_ii.write_file(&[8; 100], FileType::Text, "second");
+        let progress = raid_ii.start_reshape(16).unwrap();
+        assert_eq!((progress.reshaped_bytes, progress.total_bytes), (0, 200));
+        assert_eq!(raid_ii.status().state, ArrayState::Reshaping);
+        assert_eq!(raid_ii.start_reshape(24), Err(GeometryError::Busy));
+
+        assert_eq!(
+            raid_ii.reshape_step(99).unwrap().unwrap().reshaped_bytes,
+            100
+        );
+        assert_eq!(
+            raid_ii.reshape_step(50).unwrap().unwrap().reshaped_bytes,
+            150
+        );
+        // Writes during the reshape go to the old disks and are copied again
+        raid_ii.write_file(b"late", FileType::Text, "third");
+        assert_eq!(raid_ii.delete_file("second"), Ok(()));
+        assert_eq!(raid_ii.start_reshape(16).unwrap().reshaped_bytes, 100);
+        assert_eq!(raid_ii.total_disks(), 13);
+
+        assert_eq!(
+            raid_ii.reshape_step(1000).unwrap().unwrap().reshaped_bytes,
+            204
+        );
+        assert_eq!(raid_ii.reshape_step(1), Ok(None));
+        assert_eq!(raid_ii.total_disks(), 22);
+        assert_eq!(read_text(&mut raid_ii, "first"), Some(vec![7; 100]));
+        assert_eq!(read_text(&mut raid_ii, "third"), Some(b"late".to_vec()));
+    }
+
+    #[test]
+    fn reshape_errors_test() {
+        let mut raid_ii = RaidII::from_data_capacity(64);
+        assert_eq!(
+            raid_ii.start_reshape(0),
+            Err(GeometryError::NotWholeBytes { data_disks: 0 })
+        );
+        raid_ii.write_file(&[1; 600], FileType::Text, "small");
+        let progress = raid_ii.start_reshape(64).unwrap();
+        assert_eq!(raid_ii.start_reshape(64), Ok(progress));
+        raid_ii.reshape_step(usize::MAX).unwrap();
+        raid_ii.write_file(&[2; 600], FileType::Text, "large");
+        assert_eq!(
+            raid_ii.start_reshape(8),
+            Err(GeometryError::NotEnoughSpace {
+                needed: 1200,
+                capacity: 64 * 13
+            })
+        );
+        raid_ii.fail_disk(1);
+        raid_ii.replace_disk(1);
+        assert_eq!(raid_ii.start_reshape(16), Err(GeometryError::Busy));
+
+        // Files written during a reshape must fit the smaller array too
+        let mut raid_ii = RaidII::with_data_disks(64, 16, true).unwrap();
+        raid_ii.write_file(&[1; 800], FileType::Text, "first");
+        raid_ii.start_reshape(8).unwrap();
+        assert_eq!(raid_ii.free_space(), 64 * 13 - 800);
+        assert_eq!(
+            raid_ii.write_file(&[2; 100], FileType::Text, "second"),
+            FileWriteResult::NotEnoughSpace
+        );
+        assert_eq!(raid_ii.reshape(8).unwrap().reshaped_bytes, 800);
+        assert_eq!(raid_ii.read_file("second"), FileReadResult::NotFound);
+
+        // Written blocks must fit the smaller array too
+        let mut raid_ii = RaidII::with_data_disks(64, 16, true).unwrap();
+        raid_ii.write_block(1, &[1; DEFAULT_BLOCK_SIZE]).unwrap();
+        assert_eq!(
+            raid_ii.start_reshape(8),
+            Err(GeometryError::NotEnoughSpace {
+                needed: 2 * DEFAULT_BLOCK_SIZE,
+                capacity: 64 * 13
+            })
+        );
+
+        // An older state with more files stops the reshape until there is room again
+        let mut raid_ii = RaidII::with_data_disks(64, 16, true).unwrap();
+        raid_ii.write_file(&[1; 900], FileType::Text, "first");
+        let id = raid_ii.snapshot();
+        raid_ii.delete_file("first").unwrap();
+        raid_ii.start_reshape(8).unwrap();
+        assert!(raid_ii.restore(id));
+        assert_eq!(raid_ii.free_space(), 0);
+        let error = GeometryError::NotEnoughSpace {
+            needed: 900,
+            capacity: 64 * 13,
+        };
+        assert_eq!(raid_ii.reshape_step(usize::MAX), Err(error));
+        assert!(raid_ii.reshape_progress().is_some());
+        assert_eq!(raid_ii.delete_file("first"), Ok(()));
+        raid_ii.reshape(8).unwrap();
+        assert_eq!(raid_ii.total_disks(), 13);
+        assert_eq!(raid_ii.free_space(), 64 * 13);
+
+        // A file with a stripe which can not be decoded is not copied as valid zeros
+        let mut raid_ii = RaidII::from_data_capacity(64);
+        raid_ii.write_file(b"text", FileType::Text, "text");
+        raid_ii.flip_bit(2, 1);
+        raid_ii.flip_bit(3, 1);
+        assert_eq!(
+            raid_ii.reshape(16),
+            Err(GeometryError::Uncorrectable { position: 1 })
+        );
+        assert_eq!(raid_ii.total_disks(), 13);
+        assert_eq!(raid_ii.read_file("text"), FileReadResult::DisksCorrupted);
+        // Once the file is gone, the stripe is free space and becomes zeros
+        assert_eq!(raid_ii.delete_file("text"), Ok(()));
+        raid_ii.reshape(16).unwrap();
+        assert_eq!(raid_ii.total_disks(), 22);
+        assert_eq!(raid_ii.decode_range(0, 4).bytes, [0; 4]);
+    }
+
+    #[test]
+    fn undo_redo_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        assert_eq!(
+            raid_ii.journaled("write greeting", |raid| {
+                raid.write_file(b"Hello", FileType::Text, "greeting")
+            }),
+            FileWriteResult::Success
+        );
+        assert!(raid_ii.journaled("corrupt 3", |raid| raid.corrupt_disk(3)));
+        assert!(raid_ii.journaled("corrupt 5", |raid| raid.corrupt_disk(5)));
+        // Operations which fail are not journaled
+        assert!(!raid_ii.journaled("corrupt 99", |raid| raid.corrupt_disk(99)));
+        assert_eq!(
+            raid_ii.undo_operations().collect::<Vec<_>>(),
+            ["corrupt 5", "corrupt 3", "write greeting"]
+        );
+        assert_eq!(
+            raid_ii.read_file("greeting"),
+            FileReadResult::DisksCorrupted
+        );
+
+        assert_eq!(raid_ii.undo().as_deref(), Some("corrupt 5"));
+        assert_eq!(
+            raid_ii.read_file("greeting"),
+            FileReadResult::Success(FileType::Text, b"Hello".to_vec())
+        );
+        assert_eq!(raid_ii.redo().as_deref(), Some("corrupt 5"));
+        assert_eq!(raid_ii.redo(), None);
+        assert_eq!(raid_ii.undo().as_deref(), Some("corrupt 5"));
+        assert_eq!(raid_ii.undo().as_deref(), Some("corrupt 3"));
+        assert_eq!(raid_ii.undo().as_deref(), Some("write greeting"));
+        assert_eq!(raid_ii.undo(), None);
+        assert_eq!(raid_ii.files().count(), 0);
+
+        // A new operation drops what could be redone
+        assert_eq!(raid_ii.redo().as_deref(), Some("write greeting"));
+        assert_eq!(
+            raid_ii.journaled("delete greeting", |raid| raid.delete_file("greeting")),
+            Ok(())
+        );
+        assert_eq!(raid_ii.redo(), None);
+        assert_eq!(raid_ii.undo().as_deref(), Some("delete greeting"));
+        assert_eq!(
+            raid_ii.read_file("greeting"),
+            FileReadResult::Success(FileType::Text, b"Hello".to_vec())
+        );
+
+        for _ in 0..JOURNAL_LENGTH + 5 {
+            raid_ii.journaled("corrupt 1", |raid| raid.corrupt_disk(1));
+        }
+        assert_eq!(raid_ii.undo_operations().count(), JOURNAL_LENGTH);
+    }
+
+    #[test]
+    fn hot_spare_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        raid_ii.write_file(&data, FileType::Text, "data");
+        raid_ii.write_file(&[0; 1000], FileType::Text, "zeros");
+        raid_ii.set_hot_spares(2);
+
+        raid_ii.fail_disk(3);
+        let status = raid_ii.status();
+        assert_eq!(status.state, ArrayState::Rebuilding);
+        assert_eq!(status.disks[2].state, DiskState::Replaced);
+        assert_eq!(status.hot_spares, 1);
+        let kinds: Vec<&EventKind> = raid_ii.events().iter().map(|event| &event.kind).collect();
+        assert_eq!(
+            kinds[kinds.len() - 3..],
+            [
+                &EventKind::DiskFailed { disk_number: 3 },
+                &EventKind::SpareActivated { disk_number: 3 },
+                &EventKind::DiskReplaced { disk_number: 3 }
+            ]
+        );
+
+        // Another failure waits for the first rebuild. The stripes of the data are rebuilt
+        // already, the last ones hold zeros, so there is never more than one invalid bit.
+        raid_ii.rebuild_step(1800);
+        raid_ii.fail_disk(8);
+        assert_eq!(raid_ii.status().rebuild.unwrap().disk_number, 3);
+        assert_eq!(raid_ii.status().hot_spares, 1);
+
+        // Reads go on and rebuild in the background, the second disk follows the first one
+        assert_eq!(
+            raid_ii.read_file("data"),
+            FileReadResult::Success(FileType::Text, data.clone())
+        );
+        assert_eq!(raid_ii.status().rebuild.unwrap().disk_number, 8);
+        assert_eq!(raid_ii.status().hot_spares, 0);
+        for _ in 0..8 {
+            assert_eq!(
+                raid_ii.read_file("data"),
+                FileReadResult::Success(FileType::Text, data.clone())
+            );
+        }
+        let status = raid_ii.status();
+        assert_eq!(status.state, ArrayState::Optimal);
+        assert_eq!(status.disks[7].state, DiskState::Replaced);
+        assert_eq!(status.disks[2].corrected_errors, 0);
+
+        // Without spares a failure is left to the user
+        raid_ii.fail_disk(5);
+        assert_eq!(raid_ii.status().state, ArrayState::Degraded);
+    }
+
+    #[test]
+    fn suspect_failover_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello, Rust!", FileType::Text, "greeting");
+        raid_ii.set_suspect_threshold(5);
+        raid_ii.corrupt_disk(6);
+        raid_ii.set_hot_spares(1);
+        assert_eq!(raid_ii.status().hot_spares, 1);
+
+        assert_eq!(
+            raid_ii.read_file("greeting"),
+            FileReadResult::Success(FileType::Text, b"Hello, Rust!".to_vec())
+        );
+        // The read found the disk suspect, then rebuilt its replacement in the background
+        assert!(raid_ii
+            .events()
+            .iter()
+            .any(|event| event.kind == EventKind::SpareActivated { disk_number: 6 }));
+        let status = raid_ii.status();
+        assert_eq!(status.state, ArrayState::Optimal);
+        assert_eq!(status.disks[5].state, DiskState::Replaced);
+        assert_eq!(status.hot_spares, 0);
+    }
+
+    #[test]
+    fn array_failed_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello", FileType::Text, "greeting");
+        raid_ii.fail_disk(2);
+        raid_ii.fail_disk(12);
+        assert_eq!(raid_ii.status().state, ArrayState::Failed);
+    }
+
+    #[test]
+    fn events_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hi", FileType::Text, "greeting");
+        raid_ii.corrupt_disk(4);
+        raid_ii.read_file("greeting");
+        raid_ii.corrupt_disk(1);
+        raid_ii.corrupt_disk(2);
+        raid_ii.read_file("greeting");
+
+        let kinds: Vec<&EventKind> = raid_ii.events().iter().map(|event| &event.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &EventKind::FileWritten {
+                    name: "greeting".to_owned(),
+                    size: 2
+                },
+                &EventKind::DiskCorrupted { disk_number: 4 },
+                &EventKind::Corrected {
+                    disk_number: 4,
+                    position: 0
+                },
+                &EventKind::Corrected {
+                    disk_number: 4,
+                    position: 1
+                },
+                &EventKind::FileRead {
+                    name: "greeting".to_owned(),
+                    corrected_bits: 2
+                },
+                &EventKind::DiskCorrupted { disk_number: 1 },
+                &EventKind::DiskCorrupted { disk_number: 2 },
+                &EventKind::Uncorrectable { position: 0 },
+            ]
+        );
+    }
+
+    fn read_text(raid_ii: &mut RaidII, name: &str) -> Option<Vec<u8>> {
+        match raid_ii.read_file(name) {
+            FileReadResult::Success(_, bytes) => Some(bytes),
+            _ => None,
         }
     }
+
+    #[test]
+    fn sec_raid_test() {
+        let mut raid_ii = RaidII::new(1024, false);
+        assert!(raid_ii.parity_bit_disk.is_none());
+        assert_eq!(raid_ii.total_disks(), 12);
+        assert_eq!(raid_ii.status().disks.len(), 12);
+
+        let bytes = b"Hello, Rust!".to_vec();
+        raid_ii.write_file(&bytes, FileType::Text, "greeting");
+        assert_eq!(raid_ii.disk(12).len(), bytes.len());
+        assert!(!raid_ii.corrupt_disk(13));
+        for disk_number in 1..=12 {
+            raid_ii.corrupt_disk(disk_number);
+            assert_eq!(read_text(&mut raid_ii, "greeting"), Some(bytes.clone()));
+        }
+    }
+
+    #[test]
+    fn sec_raid_miscorrection_test() {
+        let bytes = b"Hello, Rust!".to_vec();
+        let mut miscorrected = 0;
+        for first in 1..=12 {
+            for second in first + 1..=12 {
+                let mut raid_ii = RaidII::new(1024, false);
+                raid_ii.set_checksums(false);
+                raid_ii.write_file(&bytes, FileType::Text, "greeting");
+                raid_ii.corrupt_disk(first);
+                raid_ii.corrupt_disk(second);
+                // Two errors look like one error on a third disk
+                if let Some(read_bytes) = read_text(&mut raid_ii, "greeting") {
+                    assert_ne!(read_bytes, bytes);
+                    miscorrected += 1;
+                }
+
+                // The same damage is detected when there is the parity bit disk
+                let mut raid_ii = RaidII::new(1024, true);
+                raid_ii.write_file(&bytes, FileType::Text, "greeting");
+                raid_ii.corrupt_disk(first + 1);
+                raid_ii.corrupt_disk(second + 1);
+                assert!(matches!(
+                    raid_ii.read_file("greeting"),
+                    FileReadResult::DisksCorrupted
+                ));
+            }
+        }
+        assert!(miscorrected > 0);
+    }
+
+    #[test]
+    fn silent_corruption_test() {
+        let bytes = b"Hello, Rust!".to_vec();
+        let mut raid_ii = RaidII::new(1024, false);
+        raid_ii.set_checksums(false);
+        raid_ii.write_file(&bytes, FileType::Text, "unchecked");
+        raid_ii.set_checksums(true);
+        raid_ii.write_file(&bytes, FileType::Text, "checked");
+        let files: Vec<&File> = raid_ii.files().collect();
+        assert_eq!(files[0].checksum(), None);
+        assert_eq!(files[1].checksum(), Some(crc32(&bytes)));
+
+        raid_ii.corrupt_disk(1);
+        raid_ii.corrupt_disk(2);
+        let disks_before: Vec<Disk> = (1..=12).map(|n| raid_ii.disk(n).clone()).collect();
+
+        assert!(matches!(
+            raid_ii.read_file("checked"),
+            FileReadResult::SilentCorruptionDetected
+        ));
+        // Wrong corrections are not written to the disks
+        let disks_after: Vec<Disk> = (1..=12).map(|n| raid_ii.disk(n).clone()).collect();
+        assert_eq!(disks_before, disks_after);
+        assert_eq!(
+            raid_ii.events().last().unwrap().kind,
+            EventKind::SilentCorruption {
+                name: "checked".to_owned()
+            }
+        );
+
+        match raid_ii.read_file("unchecked") {
+            FileReadResult::Success(_, read_bytes) => assert_ne!(read_bytes, bytes),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn secded_triple_error_test() {
+        // Three errors look like one to SECDED, the checksum still notices them
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let bytes = b"Hello, Rust!".to_vec();
+        raid_ii.write_file(&bytes, FileType::Text, "greeting");
+        raid_ii.corrupt_disk(2);
+        raid_ii.corrupt_disk(3);
+        raid_ii.corrupt_disk(4);
+        assert!(matches!(
+            raid_ii.read_file("greeting"),
+            FileReadResult::SilentCorruptionDetected
+        ));
+    }
+
+    #[test]
+    fn blocks_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        assert_eq!(raid_ii.block_size(), DEFAULT_BLOCK_SIZE);
+        assert_eq!(raid_ii.block_count(), 26);
+
+        let mut block = vec![0xFF; DEFAULT_BLOCK_SIZE];
+        raid_ii.read_block(10, &mut block).unwrap();
+        assert!(block.iter().all(|byte| *byte == 0));
+
+        let written: Vec<u8> = (0..DEFAULT_BLOCK_SIZE).map(|i| (i * 7) as u8).collect();
+        raid_ii.write_block(25, &written).unwrap();
+        raid_ii.corrupt_disk(4);
+        raid_ii.read_block(25, &mut block).unwrap();
+        assert_eq!(block, written);
+        // The bits are restored by the first read
+        assert_eq!(
+            raid_ii.status().disks[3].corrected_errors,
+            DEFAULT_BLOCK_SIZE
+        );
+        raid_ii.read_block(25, &mut block).unwrap();
+        assert_eq!(
+            raid_ii.status().disks[3].corrected_errors,
+            DEFAULT_BLOCK_SIZE
+        );
+
+        assert_eq!(
+            raid_ii.read_block(26, &mut block),
+            Err(BlockError::OutOfRange {
+                lba: 26,
+                block_count: 26
+            })
+        );
+        assert_eq!(
+            raid_ii.write_block(0, &[1, 2, 3]),
+            Err(BlockError::WrongBufferSize {
+                expected: DEFAULT_BLOCK_SIZE,
+                actual: 3
+            })
+        );
+
+        raid_ii.corrupt_disk(1);
+        raid_ii.corrupt_disk(2);
+        assert_eq!(
+            raid_ii.read_block(25, &mut block),
+            Err(BlockError::Uncorrectable {
+                lba: 25,
+                position: 25 * DEFAULT_BLOCK_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn files_and_blocks_test() {
+        // Files and blocks are separate regions, neither of them overwrites the other
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hello, Rust!", FileType::Text, "greeting");
+        let mut block = vec![0; DEFAULT_BLOCK_SIZE];
+        assert_eq!(
+            raid_ii.read_block(0, &mut block),
+            Err(BlockError::FileSpace { lba: 0 })
+        );
+        assert_eq!(
+            raid_ii.write_block(0, &block),
+            Err(BlockError::FileSpace { lba: 0 })
+        );
+
+        block[..4].copy_from_slice(b"RAID");
+        raid_ii.write_block(2, &block).unwrap();
+        assert_eq!(raid_ii.free_space(), 2 * DEFAULT_BLOCK_SIZE - 12);
+        assert!(matches!(
+            raid_ii.write_file(&[1; 1100], FileType::Text, "large"),
+            FileWriteResult::NotEnoughSpace
+        ));
+        assert!(matches!(
+            raid_ii.write_file(&[1; 1000], FileType::Text, "large"),
+            FileWriteResult::Success
+        ));
+        assert_eq!(
+            read_text(&mut raid_ii, "greeting").unwrap(),
+            b"Hello, Rust!"
+        );
+        let mut read = vec![0; DEFAULT_BLOCK_SIZE];
+        raid_ii.read_block(2, &mut read).unwrap();
+        assert_eq!(read, block);
+    }
+
+    #[test]
+    fn timing_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        let timing = DiskTiming::default();
+        // 100 bytes on every disk
+        let seek_and_transfer = timing.seek_time + timing.transfer_time(100);
+        assert_eq!(raid_ii.access_time(800), timing.access_time(100));
+        let (result, first) = raid_ii.write_file_timed(&[7; 800], FileType::Text, "small");
+        assert_eq!(result, FileWriteResult::Success);
+        assert!(seek_and_transfer <= first && first < seek_and_transfer + timing.rotation_time());
+
+        let slow = DiskTiming {
+            seek_time: Duration::from_millis(30),
+            ..timing
+        };
+        assert!(raid_ii.set_disk_timing(5, slow));
+        assert!(!raid_ii.set_disk_timing(14, slow));
+        assert_eq!(raid_ii.disk_timing(5), Some(slow));
+        let (_, second) = raid_ii.read_file_timed("small");
+        assert!(second >= slow.seek_time + slow.transfer_time(100));
+        assert_eq!(raid_ii.simulated_time(), first + second);
+
+        // A failed disk does not slow the array down
+        raid_ii.fail_disk(5);
+        assert_eq!(raid_ii.access_time(800), timing.access_time(100));
+        assert_eq!(raid_ii.read_file_timed("missing").1, Duration::ZERO);
+    }
+
+    #[test]
+    fn spindle_test() {
+        let timing = DiskTiming::default();
+        let mut synchronized = RaidII::from_data_capacity(1024);
+        let mut desynchronized = RaidII::from_data_capacity(1024);
+        assert!(desynchronized.desync_disk(3, 1.5));
+        assert!(!desynchronized.desync_disk(14, 0.5));
+        assert_eq!(desynchronized.spindle_offset(3), Some(0.5));
+
+        // The data comes under the other heads half a rotation before or after disk 3
+        let seek_and_transfer = timing.seek_time + timing.transfer_time(100);
+        assert_eq!(
+            desynchronized.access_time(800),
+            seek_and_transfer + timing.rotation_time().mul_f64(0.75)
+        );
+        let (_, in_sync) = synchronized.write_file_timed(&[1; 800], FileType::Text, "a");
+        let (_, out_of_sync) = desynchronized.write_file_timed(&[1; 800], FileType::Text, "a");
+        assert!(out_of_sync > in_sync);
+
+        desynchronized.sync_spindles();
+        assert_eq!(desynchronized.spindle_offset(3), Some(0.0));
+        assert_eq!(desynchronized.access_time(800), timing.access_time(100));
+    }
+
+    // Bits of the stripe which differ from the codeword of `byte`
+    fn stripe_errors(raid_ii: &RaidII, position: usize, byte: u8) -> usize {
+        raid_ii
+            .encode_stripe(&[byte])
+            .iter()
+            .enumerate()
+            .filter(|(index, bit)| raid_ii.disk(index + 1)[position] != **bit)
+            .count()
+    }
+
+    #[derive(Clone, Debug)]
+    enum Operation {
+        Write(Vec<u8>),
+        // Index of the file, modulo the number of files
+        Read(usize),
+        CorruptDisk(usize),
+        // Disk and position, modulo the number of disks and of written positions
+        FlipBit(usize, usize),
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            3 => prop::collection::vec(any::<u8>(), 1..40).prop_map(Operation::Write),
+            3 => any::<usize>().prop_map(Operation::Read),
+            1 => any::<usize>().prop_map(Operation::CorruptDisk),
+            4 => (any::<usize>(), any::<usize>())
+                .prop_map(|(disk, position)| Operation::FlipBit(disk, position)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn faults_property_test(
+            extended in any::<bool>(),
+            operations in prop::collection::vec(operation(), 1..40),
+        ) {
+            let mut raid_ii = RaidII::new(64, extended);
+            let disks = raid_ii.total_disks();
+            // Name, first position and content of every file
+            let mut files: Vec<(String, usize, Vec<u8>)> = Vec::new();
+            let mut written = 0;
+
+            for operation in operations {
+                match operation {
+                    Operation::Write(data) => {
+                        let name = format!("file {}", files.len());
+                        let fits = raid_ii.free_space() > data.len();
+                        let result = raid_ii.write_file(&data, FileType::Text, &name);
+                        if fits {
+                            prop_assert_eq!(result, FileWriteResult::Success);
+                            files.push((name, written, data.clone()));
+                            written += data.len();
+                        } else {
+                            prop_assert_eq!(result, FileWriteResult::NotEnoughSpace);
+                        }
+                    }
+                    Operation::Read(index) if !files.is_empty() => {
+                        let (name, start, data) = &files[index % files.len()];
+                        let errors = data
+                            .iter()
+                            .enumerate()
+                            .map(|(offset, byte)| stripe_errors(&raid_ii, start + offset, *byte))
+                            .max()
+                            .unwrap_or(0);
+                        match raid_ii.read_file(name) {
+                            // Wrong data is never returned
+                            FileReadResult::Success(_, bytes) => prop_assert_eq!(&bytes, data),
+                            result => prop_assert!(
+                                errors > 1,
+                                "{:?} with at most one error per stripe",
+                                result
+                            ),
+                        }
+                    }
+                    Operation::CorruptDisk(disk) => {
+                        raid_ii.corrupt_disk(disk % disks + 1);
+                    }
+                    Operation::FlipBit(disk, position) if written > 0 => {
+                        prop_assert!(raid_ii.flip_bit(disk % disks + 1, position % written));
+                    }
+                    Operation::Read(_) | Operation::FlipBit(..) => (),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flip_bit_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"Hi", FileType::Text, "greeting");
+        assert!(raid_ii.flip_bit(4, 1));
+        assert!(!raid_ii.flip_bit(4, 2));
+        assert!(!raid_ii.flip_bit(14, 0));
+        assert_eq!(stripe_errors(&raid_ii, 1, b'i'), 1);
+        assert_eq!(read_text(&mut raid_ii, "greeting").unwrap(), b"Hi");
+        assert_eq!(stripe_errors(&raid_ii, 1, b'i'), 0);
+    }
 }
@@ -0,0 +1,317 @@
+use crate::raid::{FileReadResult, FileType, RaidII};
+
+pub const HOURS_PER_YEAR: f64 = 365.25 * 24.0;
+
+/// Per-disk rates, events per hour
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FailureRates {
+    pub failure_rate: f64,
+    // A failed disk is replaced and rebuilt at this rate, one disk at a time
+    pub repair_rate: f64,
+}
+
+impl FailureRates {
+    pub fn from_mean_times(mttf_hours: f64, mttr_hours: f64) -> Self {
+        FailureRates {
+            failure_rate: 1.0 / mttf_hours,
+            repair_rate: 1.0 / mttr_hours,
+        }
+    }
+}
+
+/// How many failed disks `RaidII` survives, with or without the parity disk. A failed disk
+/// reads as zeros, the code corrects it as a single error on every stripe, a second one
+/// is beyond the code.
+pub const TOLERATED_FAILURES: usize = 1;
+
+/// Mean time to data loss in hours, from the Markov chain where the state is the number
+/// of failed disks: every working disk fails at `failure_rate`, the single repair
+/// finishes at `repair_rate`, and the data is lost one failure after `tolerated`.
+/// An array without disks holds no data to lose, its MTTDL is infinite.
+pub fn mttdl(disks: usize, tolerated: usize, rates: &FailureRates) -> f64 {
+    if disks == 0 {
+        return f64::INFINITY;
+    }
+    // The expected time to go from `k` to `k + 1` failed disks, `D_k = T_k - T_(k+1)`,
+    // follows from `D_k = (1 + repair_rate * D_(k-1)) / failure_rate_k`
+    let mut total = 0.0;
+    let mut previous: f64 = 0.0;
+    for failed in 0..=tolerated.min(disks.saturating_sub(1)) {
+        let failure_rate = (disks - failed) as f64 * rates.failure_rate;
+        let repair_rate = if failed == 0 { 0.0 } else { rates.repair_rate };
+        previous = (1.0 + repair_rate * previous) / failure_rate;
+        total += previous;
+    }
+    total
+}
+
+/// `mttdl` of the geometry of the array
+pub fn array_mttdl(raid: &RaidII, rates: &FailureRates) -> f64 {
+    mttdl(raid.total_disks(), TOLERATED_FAILURES, rates)
+}
+
+/// Probability to lose data in `hours`, starting with every disk working, in the Markov
+/// chain of `mttdl`. The chain is integrated numerically with steps much shorter than
+/// the time between its transitions.
+pub fn loss_probability(disks: usize, tolerated: usize, rates: &FailureRates, hours: f64) -> f64 {
+    let lost = tolerated.min(disks.saturating_sub(1)) + 1;
+    let failure_rate = |failed: usize| (disks - failed) as f64 * rates.failure_rate;
+    let repair_rate = |failed: usize| if failed == 0 { 0.0 } else { rates.repair_rate };
+    // Probability of every number of failed disks, the last state is the data loss
+    let derivative = |p: &[f64]| -> Vec<f64> {
+        let mut change = vec![0.0; p.len()];
+        for failed in 0..lost {
+            let to_next = p[failed] * failure_rate(failed);
+            let to_previous = p[failed] * repair_rate(failed);
+            change[failed] -= to_next + to_previous;
+            change[failed + 1] += to_next;
+            if failed > 0 {
+                change[failed - 1] += to_previous;
+            }
+        }
+        change
+    };
+
+    let fastest = failure_rate(0) + rates.repair_rate;
+    let steps = (hours * fastest * 20.0).ceil().max(1.0);
+    let step = hours / steps;
+    let mut p = vec![0.0; lost + 1];
+    p[0] = 1.0;
+    // Runge-Kutta of the fourth order
+    for _ in 0..steps as u64 {
+        let shifted = |p: &[f64], k: &[f64], scale: f64| -> Vec<f64> {
+            p.iter().zip(k).map(|(p, k)| p + k * scale).collect()
+        };
+        let k1 = derivative(&p);
+        let k2 = derivative(&shifted(&p, &k1, step / 2.0));
+        let k3 = derivative(&shifted(&p, &k2, step / 2.0));
+        let k4 = derivative(&shifted(&p, &k3, step));
+        for i in 0..p.len() {
+            p[i] += step / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+        }
+    }
+    p[lost]
+}
+
+/// `loss_probability` of the geometry of the array
+pub fn array_loss_probability(raid: &RaidII, rates: &FailureRates, years: f64) -> f64 {
+    loss_probability(
+        raid.total_disks(),
+        TOLERATED_FAILURES,
+        rates,
+        years * HOURS_PER_YEAR,
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationReport {
+    pub trials: usize,
+    pub data_losses: usize,
+    pub failures: usize,
+    pub repairs: usize,
+}
+
+impl SimulationReport {
+    pub fn loss_probability(&self) -> f64 {
+        self.data_losses as f64 / self.trials.max(1) as f64
+    }
+}
+
+// Stripes of the arrays simulated by `simulate`, small to keep the trials fast
+const SIMULATED_DISK_SIZE: usize = 8;
+const SIMULATED_FILE_SIZE: usize = 64;
+
+/// Runs `trials` lifetimes of `years` of an array with the geometry of `raid`.
+///
+/// Every trial writes a random file to a new array, then disks fail with `fail_disk` and
+/// are brought back with `replace_disk` and `rebuild` at random times. The data is lost
+/// when the file can not be read back after a failure. Same seed, same report.
+pub fn simulate(
+    raid: &RaidII,
+    rates: &FailureRates,
+    years: f64,
+    trials: usize,
+    seed: u64,
+) -> SimulationReport {
+    let mut random = Random::new(seed);
+    let mut report = SimulationReport {
+        trials,
+        data_losses: 0,
+        failures: 0,
+        repairs: 0,
+    };
+    for _ in 0..trials {
+        if simulate_trial(
+            raid,
+            rates,
+            years * HOURS_PER_YEAR,
+            &mut random,
+            &mut report,
+        ) {
+            report.data_losses += 1;
+        }
+    }
+    report
+}
+
+// Returns whether the data was lost
+fn simulate_trial(
+    template: &RaidII,
+    rates: &FailureRates,
+    horizon: f64,
+    random: &mut Random,
+    report: &mut SimulationReport,
+) -> bool {
+    let code = template.code();
+    let mut raid =
+        match RaidII::with_data_disks(SIMULATED_DISK_SIZE, code.data_bits(), code.extended()) {
+            Ok(raid) => raid,
+            Err(_) => unreachable!("the template has the same geometry"),
+        };
+    let data: Vec<u8> = (0..SIMULATED_FILE_SIZE)
+        .map(|_| random.next() as u8)
+        .collect();
+    raid.write_file(&data, FileType::Text, "data");
+
+    // Time of the next failure of every working disk, None for a failed one
+    let mut failures: Vec<Option<f64>> = (0..raid.total_disks())
+        .map(|_| Some(random.exponential(rates.failure_rate)))
+        .collect();
+    let mut waiting_repair = Vec::new();
+    let mut repair: Option<(usize, f64)> = None;
+
+    loop {
+        let now;
+        let next_failure = failures
+            .iter()
+            .enumerate()
+            .filter_map(|(index, time)| time.map(|time| (index + 1, time)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let failure_first = match (next_failure, repair) {
+            (Some((_, failure_time)), Some((_, repair_time))) => failure_time < repair_time,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => return true,
+        };
+
+        if failure_first {
+            let Some((disk_number, time)) = next_failure else {
+                unreachable!("a failure comes first only when there is one")
+            };
+            if time > horizon {
+                return false;
+            }
+            now = time;
+            raid.fail_disk(disk_number);
+            report.failures += 1;
+            failures[disk_number - 1] = None;
+            waiting_repair.push(disk_number);
+            if raid.read_file("data") != FileReadResult::Success(FileType::Text, data.clone()) {
+                return true;
+            }
+        } else {
+            let Some((disk_number, time)) = repair else {
+                unreachable!("a repair comes first only when there is one")
+            };
+            if time > horizon {
+                return false;
+            }
+            now = time;
+            raid.replace_disk(disk_number);
+            raid.rebuild();
+            report.repairs += 1;
+            failures[disk_number - 1] = Some(now + random.exponential(rates.failure_rate));
+            repair = None;
+        }
+
+        if repair.is_none() && !waiting_repair.is_empty() {
+            let disk_number = waiting_repair.remove(0);
+            repair = Some((disk_number, now + random.exponential(rates.repair_rate)));
+        }
+    }
+}
+
+// xorshift64*, there is no need for a random crate here
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // The state must not be zero, xorshift would only return zeros
+        let state = match seed ^ 0x9E3779B97F4A7C15 {
+            0 => 0x9E3779B97F4A7C15,
+            state => state,
+        };
+        Random { state }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // Uniform in (0, 1]
+    fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    fn exponential(&mut self, rate: f64) -> f64 {
+        -self.uniform().ln() / rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reliability::*;
+
+    #[test]
+    fn mttdl_test() {
+        let rates = FailureRates::from_mean_times(100_000.0, 24.0);
+        let (n, failure, repair) = (13.0, rates.failure_rate, rates.repair_rate);
+        let expected = ((2.0 * n - 1.0) * failure + repair) / (n * (n - 1.0) * failure * failure);
+        let raid_ii = RaidII::from_data_capacity(16);
+        assert!((array_mttdl(&raid_ii, &rates) - expected).abs() / expected < 1e-12);
+
+        // Without redundancy the first failure loses the data
+        assert!((mttdl(13, 0, &rates) - 100_000.0 / 13.0).abs() < 1e-6);
+        assert!(mttdl(13, 2, &rates) > mttdl(13, 1, &rates));
+        assert!(mttdl(12, 1, &rates) > mttdl(13, 1, &rates));
+        assert_eq!(mttdl(0, 1, &rates), f64::INFINITY);
+
+        // Losses are rare, then they come as a Poisson process with the mean time of `mttdl`
+        let hours = 10.0 * HOURS_PER_YEAR;
+        let poisson = 1.0 - (-hours / expected).exp();
+        let probability = loss_probability(13, 1, &rates, hours);
+        assert!((probability - poisson).abs() / poisson < 0.01);
+        assert!((loss_probability(13, 0, &rates, 1.0) - (1.0 - (-13e-5f64).exp())).abs() < 1e-12);
+    }
+
+    #[test]
+    fn simulate_test() {
+        // Fast failures, so that data is lost often enough to measure
+        let rates = FailureRates::from_mean_times(1000.0, 100.0);
+        let raid_ii = RaidII::from_data_capacity(16);
+        let years = 100.0 / HOURS_PER_YEAR;
+        let report = simulate(&raid_ii, &rates, years, 1000, 1);
+        assert_eq!(report, simulate(&raid_ii, &rates, years, 1000, 1));
+        assert!(report.failures >= report.data_losses);
+
+        let expected = array_loss_probability(&raid_ii, &rates, years);
+        assert!(
+            (report.loss_probability() - expected).abs() < 0.05,
+            "simulated {}, expected {}",
+            report.loss_probability(),
+            expected
+        );
+    }
+
+    #[test]
+    fn random_test() {
+        // This seed cancels the constant, the generator must not get stuck at zero
+        let mut random = Random::new(0x9E3779B97F4A7C15);
+        assert!((0..4).any(|_| random.next() != 0));
+    }
+}
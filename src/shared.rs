@@ -0,0 +1,249 @@
+use crate::raid::{
+    ArrayStatus, DecodedFile, File, FileReadResult, FileType, FileWriteResult, RaidII,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// `RaidII` which can be shared between threads, clones refer to the same array.
+///
+/// Reads decode files in parallel under a shared lock. Restoring the corrected bits
+/// needs the exclusive lock, which is taken for a short time after the decoding.
+/// Writes, fault injection and rebuilds take the exclusive lock for the whole operation.
+#[derive(Clone)]
+pub struct SharedRaid {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    raid: RwLock<RaidII>,
+    // Changed every time the disks are changed, so a read can tell whether the bits
+    // it decoded under the shared lock are still there when it restores them
+    generation: AtomicU64,
+}
+
+impl SharedRaid {
+    pub fn new(raid: RaidII) -> Self {
+        SharedRaid {
+            inner: Arc::new(Inner {
+                raid: RwLock::new(raid),
+                generation: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Runs `f` under the shared lock, other readers run at the same time
+    pub fn read<T>(&self, f: impl FnOnce(&RaidII) -> T) -> T {
+        f(&self.read_lock())
+    }
+
+    /// Runs `f` under the exclusive lock
+    pub fn write<T>(&self, f: impl FnOnce(&mut RaidII) -> T) -> T {
+        let mut raid = self.write_lock();
+        self.inner.generation.fetch_add(1, Ordering::Relaxed);
+        f(&mut raid)
+    }
+
+    pub fn read_file(&self, name: &str) -> FileReadResult {
+        let (decoded, generation) = self.decode_file(name);
+        self.finish_read(name, decoded, generation)
+    }
+
+    // Decodes the file under the shared lock, with the generation of the disks it saw
+    fn decode_file(&self, name: &str) -> (DecodedFile, u64) {
+        let raid = self.read_lock();
+        let generation = self.inner.generation.load(Ordering::Relaxed);
+        (raid.decode_file(name), generation)
+    }
+
+    // Restores the corrections under the exclusive lock, if the disks are still the ones
+    // which were decoded
+    fn finish_read(&self, name: &str, decoded: DecodedFile, generation: u64) -> FileReadResult {
+        let mut raid = self.write_lock();
+        if self.inner.generation.load(Ordering::Relaxed) != generation {
+            // The disks changed in between, the decoded corrections may be wrong now
+            self.inner.generation.fetch_add(1, Ordering::Relaxed);
+            return raid.read_file(name);
+        }
+        let (result, changed) = raid.finish_read(name, decoded);
+        if changed {
+            self.inner.generation.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn write_file(&self, data: &[u8], file_type: FileType, name: &str) -> FileWriteResult {
+        self.write(|raid| raid.write_file(data, file_type, name))
+    }
+
+    pub fn corrupt_disk(&self, disk_number: usize) -> bool {
+        self.write(|raid| raid.corrupt_disk(disk_number))
+    }
+
+    pub fn fail_disk(&self, disk_number: usize) -> bool {
+        self.write(|raid| raid.fail_disk(disk_number))
+    }
+
+    /// Replaces the disk and rebuilds it in one go
+    pub fn replace_disk(&self, disk_number: usize) -> bool {
+        self.write(|raid| {
+            let replaced = raid.replace_disk(disk_number);
+            if replaced {
+                raid.rebuild();
+            }
+            replaced
+        })
+    }
+
+    pub fn status(&self) -> ArrayStatus {
+        self.read(RaidII::status)
+    }
+
+    pub fn files(&self) -> Vec<File> {
+        self.read(|raid| raid.files().cloned().collect())
+    }
+
+    // A thread which panicked under the lock leaves the array as it was at the panic,
+    // which is no worse than what fault injection does
+    fn read_lock(&self) -> RwLockReadGuard<'_, RaidII> {
+        self.inner
+            .raid
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_lock(&self) -> RwLockWriteGuard<'_, RaidII> {
+        self.inner
+            .raid
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raid::*;
+    use crate::shared::*;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+
+    fn content(name: &str) -> Vec<u8> {
+        format!("content of {}", name).into_bytes()
+    }
+
+    #[test]
+    fn parallel_reads_test() {
+        let shared = SharedRaid::new(RaidII::from_data_capacity(1024));
+        let barrier = Barrier::new(2);
+        // Both threads hold the shared lock at the barrier, serialized readers would hang
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| shared.read(|_| barrier.wait()));
+            }
+        });
+    }
+
+    #[test]
+    fn read_after_write_test() {
+        let shared = SharedRaid::new(RaidII::from_data_capacity(1024));
+        assert_eq!(
+            shared.write_file(&content("a"), FileType::Text, "a"),
+            FileWriteResult::Success
+        );
+        shared.corrupt_disk(3);
+        let other = shared.clone();
+        let read = thread::spawn(move || other.read_file("a")).join().unwrap();
+        assert_eq!(read, FileReadResult::Success(FileType::Text, content("a")));
+        assert_eq!(
+            shared.status().disks[2].corrected_errors,
+            content("a").len()
+        );
+        assert_eq!(shared.read_file("missing"), FileReadResult::NotFound);
+    }
+
+    #[test]
+    fn read_during_rebuild_test() {
+        let shared = SharedRaid::new(RaidII::from_data_capacity(1024));
+        shared.write_file(&content("a"), FileType::Text, "a");
+        // The codeword of a zero byte is all zeros, the empty disk has no errors there
+        shared.write_file(&[0; 16], FileType::Text, "zeros");
+        shared.write(|raid| raid.fail_disk(3) && raid.replace_disk(3));
+
+        let barrier = Barrier::new(2);
+        thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                let (decoded, generation) = shared.decode_file("a");
+                barrier.wait();
+                barrier.wait();
+                shared.finish_read("a", decoded, generation)
+            });
+            // This read has nothing to correct, but it rebuilds the disk in the background,
+            // the bits the other reader is about to restore are already right
+            barrier.wait();
+            assert_eq!(
+                shared.read_file("zeros"),
+                FileReadResult::Success(FileType::Text, vec![0; 16])
+            );
+            barrier.wait();
+            assert_eq!(
+                reader.join().unwrap(),
+                FileReadResult::Success(FileType::Text, content("a"))
+            );
+        });
+
+        let health = shared.read(|raid| raid.file_health("a")).unwrap();
+        assert_eq!(health.correctable, 0);
+        assert_eq!(health.clean, content("a").len());
+    }
+
+    #[test]
+    fn stress_test() {
+        let shared = SharedRaid::new(RaidII::from_data_capacity(1024));
+        shared.write_file(&content("initial"), FileType::Text, "initial");
+
+        thread::scope(|scope| {
+            for writer in 0..2 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        let name = format!("file {} {}", writer, i);
+                        let result = shared.write_file(&content(&name), FileType::Text, &name);
+                        assert_eq!(result, FileWriteResult::Success);
+                    }
+                });
+            }
+
+            for _ in 0..4 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        for file in shared.files() {
+                            let read = shared.read_file(file.name());
+                            let expected =
+                                FileReadResult::Success(FileType::Text, content(file.name()));
+                            assert_eq!(read, expected);
+                        }
+                    }
+                });
+            }
+
+            // One disk at a time is failed and replaced, the others keep every stripe decodable
+            let shared = &shared;
+            scope.spawn(move || {
+                for round in 0..30 {
+                    let disk_number = round % 13 + 1;
+                    assert!(shared.fail_disk(disk_number));
+                    thread::sleep(Duration::from_millis(1));
+                    assert!(shared.replace_disk(disk_number));
+                }
+            });
+        });
+
+        assert_eq!(shared.files().len(), 101);
+        assert_eq!(shared.status().state, ArrayState::Optimal);
+        for file in shared.files() {
+            let expected = FileReadResult::Success(FileType::Text, content(file.name()));
+            assert_eq!(shared.read_file(file.name()), expected);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+pub const SECTOR_SIZE: u64 = 512;
+
+pub const SECTORS_PER_TRACK: u64 = 32;
+
+/// Where the sector holding the byte is on its track, as a fraction of a rotation
+pub fn sector_angle(byte_offset: u64) -> f64 {
+    (byte_offset / SECTOR_SIZE % SECTORS_PER_TRACK) as f64 / SECTORS_PER_TRACK as f64
+}
+
+/// Mechanical parameters of a disk, used to compute simulated access times.
+/// The defaults are those of a disk from the time of the RAID paper (1988).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiskTiming {
+    // Average time to move the heads to another track
+    pub seek_time: Duration,
+    pub rpm: u32,
+    // Bytes per second under the head
+    pub transfer_rate: u64,
+}
+
+impl Default for DiskTiming {
+    fn default() -> Self {
+        DiskTiming {
+            seek_time: Duration::from_millis(12),
+            rpm: 3600,
+            transfer_rate: 2_000_000,
+        }
+    }
+}
+
+impl DiskTiming {
+    pub fn rotation_time(&self) -> Duration {
+        Duration::from_secs(60) / self.rpm.max(1)
+    }
+
+    // On average the sector is half a rotation away
+    pub fn rotational_latency(&self) -> Duration {
+        self.rotation_time() / 2
+    }
+
+    // Disks transfer whole sectors
+    pub fn transfer_time(&self, bytes: u64) -> Duration {
+        let transferred = bytes.div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+        Duration::from_nanos(transferred * 1_000_000_000 / self.transfer_rate.max(1))
+    }
+
+    /// Time of a request for `bytes` at a random place of the disk
+    pub fn access_time(&self, bytes: u64) -> Duration {
+        if bytes == 0 {
+            return Duration::ZERO;
+        }
+        self.seek_time + self.rotational_latency() + self.transfer_time(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timing::*;
+
+    #[test]
+    fn access_time_test() {
+        let timing = DiskTiming::default();
+        assert_eq!(timing.rotation_time(), Duration::from_nanos(16_666_666));
+        assert_eq!(timing.transfer_time(1), Duration::from_micros(256));
+        assert_eq!(timing.transfer_time(1024), Duration::from_micros(512));
+        assert_eq!(timing.access_time(0), Duration::ZERO);
+        assert_eq!(sector_angle(511), 0.0);
+        assert_eq!(sector_angle(8 * SECTOR_SIZE), 0.25);
+        assert_eq!(sector_angle(SECTORS_PER_TRACK * SECTOR_SIZE), 0.0);
+        assert_eq!(
+            timing.access_time(100),
+            Duration::from_millis(12)
+                + Duration::from_nanos(8_333_333)
+                + Duration::from_micros(256)
+        );
+    }
+}
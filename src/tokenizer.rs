@@ -0,0 +1,245 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Token {
+    Word(Vec<u8>),
+    // `<<TAG`: the data follows on the next lines and ends with a line equal to TAG
+    Heredoc(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenizeError {
+    UnterminatedQuote(char),
+    DanglingEscape,
+    InvalidHexEscape(String),
+    EmptyHeredocTag,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::UnterminatedQuote(quote) => write!(f, "missing closing {}", quote),
+            TokenizeError::DanglingEscape => write!(f, "line ends with a single backslash"),
+            TokenizeError::InvalidHexEscape(escape) => {
+                write!(f, "'\\x{}' is not a valid byte escape", escape)
+            }
+            TokenizeError::EmptyHeredocTag => write!(f, "'<<' must be followed by a tag"),
+        }
+    }
+}
+
+/// Splits a command line into words.
+///
+/// Words are separated by whitespace. Double quotes group words and allow escapes,
+/// single quotes group words literally, a backslash outside of quotes escapes the next
+/// character. Supported escapes: `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'`, `\ ` and `\xNN`.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, TokenizeError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+
+        if first == '<' {
+            let mut lookahead = chars.clone();
+            lookahead.next();
+            if lookahead.next() == Some('<') {
+                chars.next();
+                chars.next();
+                let mut tag = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    tag.push(c);
+                }
+                if tag.is_empty() {
+                    return Err(TokenizeError::EmptyHeredocTag);
+                }
+                tokens.push(Token::Heredoc(tag));
+                continue;
+            }
+        }
+
+        let mut word = Vec::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            match c {
+                '"' => loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => read_escape(&mut chars, &mut word)?,
+                        Some(c) => push_char(&mut word, c),
+                        None => return Err(TokenizeError::UnterminatedQuote('"')),
+                    }
+                },
+                '\'' => loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => push_char(&mut word, c),
+                        None => return Err(TokenizeError::UnterminatedQuote('\'')),
+                    }
+                },
+                '\\' => read_escape(&mut chars, &mut word)?,
+                c => push_char(&mut word, c),
+            }
+        }
+        tokens.push(Token::Word(word));
+    }
+
+    Ok(tokens)
+}
+
+fn push_char(word: &mut Vec<u8>, c: char) {
+    let mut buffer = [0; 4];
+    word.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+}
+
+fn read_escape(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    word: &mut Vec<u8>,
+) -> Result<(), TokenizeError> {
+    match chars.next() {
+        Some('n') => word.push(b'\n'),
+        Some('t') => word.push(b'\t'),
+        Some('r') => word.push(b'\r'),
+        Some('0') => word.push(0),
+        Some('x') => {
+            let digits: String = (0..2).filter_map(|_| chars.next()).collect();
+            // `from_str_radix` alone would also take a sign, as in "+F"
+            if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(TokenizeError::InvalidHexEscape(digits));
+            }
+            match u8::from_str_radix(&digits, 16) {
+                Ok(byte) => word.push(byte),
+                Err(_) => return Err(TokenizeError::InvalidHexEscape(digits)),
+            }
+        }
+        // \\, \", \', escaped whitespace and any other character stand for themselves
+        Some(c) => push_char(word, c),
+        None => return Err(TokenizeError::DanglingEscape),
+    }
+    Ok(())
+}
+
+/// Renders bytes as a printable string, the reverse of the escapes accepted by `tokenize`.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                '\\' => escaped.push_str("\\\\"),
+                '"' => escaped.push_str("\\\""),
+                c if c.is_control() => {
+                    for byte in c.to_string().bytes() {
+                        escaped.push_str(&format!("\\x{:02x}", byte));
+                    }
+                }
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tokenizer::*;
+
+    fn words(line: &str) -> Vec<Vec<u8>> {
+        tokenize(line)
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                Token::Heredoc(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plain_words_test() {
+        assert_eq!(
+            words("  write data   name "),
+            vec![b"write".to_vec(), b"data".to_vec(), b"name".to_vec()]
+        );
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_test() {
+        assert_eq!(
+            words(r#"write "Hello, Rust!" 'my file'"#),
+            vec![
+                b"write".to_vec(),
+                b"Hello, Rust!".to_vec(),
+                b"my file".to_vec()
+            ]
+        );
+        assert_eq!(words(r#"a"b c"d"#), vec![b"ab cd".to_vec()]);
+        assert_eq!(words(r"'\n'"), vec![b"\\n".to_vec()]);
+    }
+
+    #[test]
+    fn escapes_test() {
+        assert_eq!(
+            words(r#""line\none" two\ words \x00\xfF"#),
+            vec![b"line\none".to_vec(), b"two words".to_vec(), vec![0, 255]]
+        );
+        assert_eq!(words(r#""say \"hi\"""#), vec![b"say \"hi\"".to_vec()]);
+    }
+
+    #[test]
+    fn heredoc_test() {
+        assert_eq!(
+            tokenize("write <<EOF notes").unwrap(),
+            vec![
+                Token::Word(b"write".to_vec()),
+                Token::Heredoc("EOF".to_owned()),
+                Token::Word(b"notes".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_test() {
+        assert_eq!(
+            tokenize("write \"abc name"),
+            Err(TokenizeError::UnterminatedQuote('"'))
+        );
+        assert_eq!(
+            tokenize("write 'abc name"),
+            Err(TokenizeError::UnterminatedQuote('\''))
+        );
+        assert_eq!(tokenize("write abc\\"), Err(TokenizeError::DanglingEscape));
+        assert_eq!(
+            tokenize("write \\xZ1 name"),
+            Err(TokenizeError::InvalidHexEscape("Z1".to_owned()))
+        );
+        assert_eq!(
+            tokenize("write \\x+F name"),
+            Err(TokenizeError::InvalidHexEscape("+F".to_owned()))
+        );
+        assert_eq!(
+            tokenize("write << name"),
+            Err(TokenizeError::EmptyHeredocTag)
+        );
+    }
+
+    #[test]
+    fn escape_bytes_test() {
+        assert_eq!(escape_bytes(b"plain text"), "plain text");
+        assert_eq!(escape_bytes(b"a\nb\\"), "a\\nb\\\\");
+        assert_eq!(escape_bytes(&[0x41, 0xff, 0x00]), "A\\xff\\x00");
+        let escaped = escape_bytes(&[b'x', 0xc3, 0x28, b'\t']);
+        assert_eq!(
+            words(&format!("\"{}\"", escaped)),
+            vec![vec![b'x', 0xc3, 0x28, b'\t']]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use crate::event_log::EventKind;
+use crate::hamming_encoding::Bit;
+use crate::raid::{FileReadResult, FileType, FileWriteResult, RaidII};
+use std::io;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(200);
+
+enum DiskRequest {
+    Read {
+        start: usize,
+        length: usize,
+        reply: Sender<DiskReply>,
+    },
+    Write {
+        start: usize,
+        bits: Vec<Bit>,
+        reply: Sender<DiskReply>,
+    },
+    Corrupt,
+    // Every following request takes this long
+    SetDelay(Duration),
+    // Requests are received, but never answered
+    Hang,
+}
+
+struct DiskReply {
+    // Position of the disk in the codeword, that is disk number - 1
+    index: usize,
+    // The bits read, empty for a write
+    bits: Vec<Bit>,
+}
+
+// A disk which serves its requests in its own thread, one after another
+struct DiskWorker {
+    requests: Sender<DiskRequest>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DiskWorker {
+    fn spawn(index: usize, disk: Vec<Bit>) -> io::Result<Self> {
+        let (requests, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name(format!("disk {}", index + 1))
+            .spawn(move || Self::run(index, disk, receiver))?;
+        Ok(DiskWorker {
+            requests,
+            thread: Some(thread),
+        })
+    }
+
+    // Stops when the controller drops the sender
+    fn run(index: usize, mut disk: Vec<Bit>, requests: Receiver<DiskRequest>) {
+        let mut delay = Duration::ZERO;
+        let mut hung = false;
+        // A hung disk keeps the requests it got, the controller only notices the timeout
+        let mut unanswered = Vec::new();
+
+        for request in requests {
+            match request {
+                DiskRequest::Read {
+                    start,
+                    length,
+                    reply,
+                } if !hung => {
+                    thread::sleep(delay);
+                    let bits = (start..start + length)
+                        .map(|position| disk.get(position).copied().unwrap_or(false))
+                        .collect();
+                    // The controller may have given up on this disk already
+                    let _ = reply.send(DiskReply { index, bits });
+                }
+                DiskRequest::Write { start, bits, reply } if !hung => {
+                    thread::sleep(delay);
+                    if disk.len() < start + bits.len() {
+                        disk.resize(start + bits.len(), false);
+                    }
+                    disk[start..start + bits.len()].copy_from_slice(&bits);
+                    let _ = reply.send(DiskReply {
+                        index,
+                        bits: Vec::new(),
+                    });
+                }
+                DiskRequest::Read { reply, .. } | DiskRequest::Write { reply, .. } => {
+                    unanswered.push(reply)
+                }
+                DiskRequest::Corrupt => disk.iter_mut().for_each(|bit| *bit = !*bit),
+                DiskRequest::SetDelay(new_delay) => delay = new_delay,
+                DiskRequest::Hang => hung = true,
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WorkerState {
+    Online,
+    // The disk did not answer in time, it is failed in the array
+    TimedOut,
+}
+
+/// What happened to the disks during an operation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IoReport {
+    pub elapsed: Duration,
+    // Disk numbers of the disks which timed out during this operation
+    pub timed_out: Vec<usize>,
+    pub corrected_bits: usize,
+}
+
+/// RAID II where every disk of the array is served by its own thread.
+///
+/// The array encodes, decodes and keeps the file table as usual, the controller moves the
+/// bits of every disk to and from its worker: a write sends the new stripes to all disks
+/// at once, a read gathers them from all disks before the array decodes them. A disk which
+/// does not answer in time is failed in the array for good, so the status, the event log
+/// and the hot spares see it like any other failure.
+pub struct ArrayController {
+    raid: RaidII,
+    workers: Vec<DiskWorker>,
+    states: Vec<WorkerState>,
+    timeout: Duration,
+}
+
+impl ArrayController {
+    /// Starts a worker for every disk of the array with the bits the disk holds
+    pub fn new(raid: RaidII, timeout: Duration) -> io::Result<Self> {
+        let stripes = raid.stored_stripes();
+        let mut workers = Vec::with_capacity(raid.total_disks());
+        for disk_number in 1..=raid.total_disks() {
+            let disk = raid.column(disk_number, 0..stripes);
+            workers.push(DiskWorker::spawn(disk_number - 1, disk)?);
+        }
+        Ok(ArrayController {
+            states: vec![WorkerState::Online; workers.len()],
+            raid,
+            workers,
+            timeout,
+        })
+    }
+
+    pub fn raid(&self) -> &RaidII {
+        &self.raid
+    }
+
+    pub fn disk_states(&self) -> &[WorkerState] {
+        &self.states
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Makes every following request to the disk take `delay`
+    pub fn set_disk_delay(&self, disk_number: usize, delay: Duration) -> bool {
+        self.send(disk_number, DiskRequest::SetDelay(delay))
+    }
+
+    /// The disk stops answering, as a hung drive does
+    pub fn hang_disk(&self, disk_number: usize) -> bool {
+        self.send(disk_number, DiskRequest::Hang)
+    }
+
+    /// Inverts every bit stored by the worker, the array sees it on the next read
+    pub fn corrupt_disk(&self, disk_number: usize) -> bool {
+        self.send(disk_number, DiskRequest::Corrupt)
+    }
+
+    pub fn write_file(&mut self, data: &[u8], name: &str) -> (FileWriteResult, IoReport) {
+        let started = Instant::now();
+        let result = self.raid.write_file(data, FileType::Text, name);
+        let mut report = IoReport::default();
+        if result == FileWriteResult::Success {
+            // The new file is the last one, there may be older files of the same name
+            if let Some(file) = self.raid.files().last() {
+                let stripes = self.raid.stripes_of(file);
+                report.timed_out = self.write_stripes(stripes);
+            }
+        }
+        report.elapsed = started.elapsed();
+        (result, report)
+    }
+
+    pub fn read_file(&mut self, name: &str) -> (FileReadResult, IoReport) {
+        let started = Instant::now();
+        let Some(stripes) = self.raid.file_stripes(name) else {
+            return (FileReadResult::NotFound, IoReport::default());
+        };
+
+        let (reply, replies) = mpsc::channel();
+        for disk_number in 1..=self.workers.len() {
+            self.send(
+                disk_number,
+                DiskRequest::Read {
+                    start: stripes.start,
+                    length: stripes.len(),
+                    reply: reply.clone(),
+                },
+            );
+        }
+        drop(reply);
+        let (columns, mut timed_out) = self.gather(replies, started);
+        for (index, bits) in columns.into_iter().enumerate() {
+            if let Some(bits) = bits {
+                self.raid.set_column(index + 1, stripes.start, &bits);
+            }
+        }
+
+        let events_before = self.raid.events().len();
+        let result = self.raid.read_file(name);
+        let corrected_bits = self.raid.events()[events_before..]
+            .iter()
+            .filter(|event| matches!(event.kind, EventKind::Corrected { .. }))
+            .count();
+        // The restored bits go back to the disks
+        if corrected_bits > 0 {
+            timed_out.extend(self.write_stripes(stripes));
+        }
+        let report = IoReport {
+            elapsed: started.elapsed(),
+            timed_out,
+            corrected_bits,
+        };
+        (result, report)
+    }
+
+    // Sends the stripes of the array to every online disk, returns the disks which timed out
+    fn write_stripes(&mut self, stripes: Range<usize>) -> Vec<usize> {
+        let started = Instant::now();
+        let (reply, replies) = mpsc::channel();
+        for disk_number in 1..=self.workers.len() {
+            self.send(
+                disk_number,
+                DiskRequest::Write {
+                    start: stripes.start,
+                    bits: self.raid.column(disk_number, stripes.clone()),
+                    reply: reply.clone(),
+                },
+            );
+        }
+        drop(reply);
+        self.gather(replies, started).1
+    }
+
+    // Sends the request to an online disk
+    fn send(&self, disk_number: usize, request: DiskRequest) -> bool {
+        if disk_number == 0 || disk_number > self.workers.len() {
+            return false;
+        }
+        match self.states[disk_number - 1] {
+            WorkerState::Online => self.workers[disk_number - 1].requests.send(request).is_ok(),
+            WorkerState::TimedOut => false,
+        }
+    }
+
+    // Waits for the answers of the online disks until the timeout runs out,
+    // the disks which did not answer are failed in the array
+    fn gather(
+        &mut self,
+        replies: Receiver<DiskReply>,
+        started: Instant,
+    ) -> (Vec<Option<Vec<Bit>>>, Vec<usize>) {
+        let deadline = started + self.timeout;
+        let mut disks = vec![None; self.workers.len()];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match replies.recv_timeout(remaining) {
+                Ok(reply) => disks[reply.index] = Some(reply.bits),
+                // Every online disk answered
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => break,
+            }
+        }
+
+        let mut timed_out = Vec::new();
+        for (index, bits) in disks.iter().enumerate() {
+            if bits.is_none() && self.states[index] == WorkerState::Online {
+                self.states[index] = WorkerState::TimedOut;
+                self.raid.fail_disk(index + 1);
+                timed_out.push(index + 1);
+            }
+        }
+        (disks, timed_out)
+    }
+}
+
+impl Drop for ArrayController {
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            // The thread ends when it sees the channel closed
+            let (closed, _) = mpsc::channel();
+            worker.requests = closed;
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raid::{ArrayState, DiskState};
+    use crate::workers::*;
+
+    fn controller(timeout: Duration) -> ArrayController {
+        ArrayController::new(RaidII::from_data_capacity(1024), timeout).unwrap()
+    }
+
+    #[test]
+    fn read_write_test() {
+        let mut controller = controller(DEFAULT_TIMEOUT);
+        let (result, report) = controller.write_file(b"Hello, Rust!", "greeting");
+        assert_eq!(result, FileWriteResult::Success);
+        assert!(report.timed_out.is_empty());
+        controller.corrupt_disk(7);
+
+        let (result, report) = controller.read_file("greeting");
+        assert_eq!(
+            result,
+            FileReadResult::Success(FileType::Text, b"Hello, Rust!".to_vec())
+        );
+        assert_eq!(report.corrected_bits, 12);
+        assert_eq!(controller.raid().status().disks[6].corrected_errors, 12);
+        // The corrections were written to the disk, the next read finds none
+        assert_eq!(controller.read_file("greeting").1.corrected_bits, 0);
+        assert_eq!(controller.read_file("missing").0, FileReadResult::NotFound);
+        assert_eq!(
+            controller.write_file(&[0; 13 * 1024], "big").0,
+            FileWriteResult::NotEnoughSpace
+        );
+        let names: Vec<&str> = controller.raid().files().map(|file| file.name()).collect();
+        assert_eq!(names, ["greeting"]);
+    }
+
+    #[test]
+    fn existing_files_test() {
+        let mut raid_ii = RaidII::from_data_capacity(1024);
+        raid_ii.write_file(b"written before", FileType::Text, "before");
+        let mut controller = ArrayController::new(raid_ii, DEFAULT_TIMEOUT).unwrap();
+        controller.corrupt_disk(3);
+        assert_eq!(
+            controller.read_file("before").0,
+            FileReadResult::Success(FileType::Text, b"written before".to_vec())
+        );
+    }
+
+    #[test]
+    fn slow_disk_test() {
+        let mut controller = controller(Duration::from_millis(500));
+        controller.set_disk_delay(4, Duration::from_millis(50));
+        let (_, report) = controller.write_file(b"slow", "slow");
+        // Every disk works at the same time, the slowest one sets the pace
+        assert!(report.elapsed >= Duration::from_millis(50));
+        assert!(report.timed_out.is_empty());
+        assert_eq!(
+            controller.read_file("slow").0,
+            FileReadResult::Success(FileType::Text, b"slow".to_vec())
+        );
+    }
+
+    #[test]
+    fn too_slow_disk_test() {
+        let mut controller = controller(Duration::from_millis(100));
+        controller.write_file(b"too slow", "slow");
+        controller.set_disk_delay(5, Duration::from_millis(1000));
+
+        // The controller stops waiting at the timeout and decodes without the disk
+        let (result, report) = controller.read_file("slow");
+        assert_eq!(
+            result,
+            FileReadResult::Success(FileType::Text, b"too slow".to_vec())
+        );
+        assert_eq!(report.timed_out, vec![5]);
+        assert!(report.elapsed >= Duration::from_millis(100));
+        assert!(report.elapsed < Duration::from_millis(1000));
+        assert_eq!(controller.raid().status().disks[4].state, DiskState::Failed);
+    }
+
+    #[test]
+    fn hung_disk_test() {
+        let mut controller = controller(Duration::from_millis(50));
+        controller.write_file(b"before", "before");
+        controller.hang_disk(2);
+
+        let (_, report) = controller.write_file(b"after", "after");
+        assert_eq!(report.timed_out, vec![2]);
+        assert!(report.elapsed >= Duration::from_millis(50));
+        assert_eq!(controller.disk_states()[1], WorkerState::TimedOut);
+        let status = controller.raid().status();
+        assert_eq!(status.state, ArrayState::Degraded);
+        assert_eq!(status.disks[1].state, DiskState::Failed);
+
+        // The failed disk is restored by the code, without waiting for it again
+        let (result, report) = controller.read_file("after");
+        assert_eq!(
+            result,
+            FileReadResult::Success(FileType::Text, b"after".to_vec())
+        );
+        assert!(report.timed_out.is_empty());
+        assert!(report.elapsed < Duration::from_millis(50));
+        assert_eq!(
+            controller.read_file("before").0,
+            FileReadResult::Success(FileType::Text, b"before".to_vec())
+        );
+
+        // A second hung disk is more than the code can correct
+        controller.hang_disk(9);
+        assert_eq!(controller.read_file("before").1.timed_out, vec![9]);
+        assert_eq!(controller.raid().status().state, ArrayState::Failed);
+    }
+}
@@ -0,0 +1,92 @@
+use raid_ii_emulation::commands::{self, Output};
+use raid_ii_emulation::*;
+
+#[test]
+fn files_test() {
+    let mut raid_ii = RaidII::new(1024, true);
+    assert_eq!(raid_ii.total_disks(), 13);
+    assert_eq!(
+        raid_ii.write_file(b"first file", FileType::Text, "first"),
+        FileWriteResult::Success
+    );
+    assert_eq!(
+        raid_ii.write_file(b"second file", FileType::Text, "second"),
+        FileWriteResult::Success
+    );
+    assert!(raid_ii.corrupt_disk(4));
+    assert_eq!(
+        raid_ii.read_file("second"),
+        FileReadResult::Success(FileType::Text, b"second file".to_vec())
+    );
+    assert!(raid_ii.corrupt_disk(9));
+    assert_eq!(raid_ii.read_file("first"), FileReadResult::DisksCorrupted);
+    assert_eq!(raid_ii.read_file("third"), FileReadResult::NotFound);
+    let names: Vec<&str> = raid_ii.files().map(|file| file.name()).collect();
+    assert_eq!(names, ["first", "second"]);
+}
+
+#[test]
+fn failed_disk_test() {
+    let mut raid_ii = RaidII::from_data_capacity(64);
+    raid_ii.write_file(b"survives a failure", FileType::Text, "data");
+    assert!(raid_ii.fail_disk(2));
+    assert_eq!(raid_ii.status().state, ArrayState::Degraded);
+    assert!(raid_ii.replace_disk(2));
+    raid_ii.rebuild();
+    assert_eq!(raid_ii.status().state, ArrayState::Optimal);
+    assert_eq!(raid_ii.status().disks[1].state, DiskState::Replaced);
+    assert_eq!(
+        raid_ii.read_file("data"),
+        FileReadResult::Success(FileType::Text, b"survives a failure".to_vec())
+    );
+}
+
+#[test]
+fn block_device_test() {
+    let mut raid_ii = RaidII::new(1024, true);
+    let device: &mut dyn BlockDevice = &mut raid_ii;
+    let block = vec![0xA5; device.block_size()];
+    device.write_block(3, &block).unwrap();
+    let mut read = vec![0; device.block_size()];
+    device.read_block(3, &mut read).unwrap();
+    assert_eq!(read, block);
+    let lba = device.block_count();
+    assert!(matches!(
+        device.read_block(lba, &mut read),
+        Err(BlockError::OutOfRange { .. })
+    ));
+}
+
+#[test]
+fn codec_test() {
+    let code = HammingCode::hamming_72_64();
+    let data: Vec<Bit> = (0..64).map(|i| i % 5 == 0).collect();
+    let mut codeword = code.encode(&data).unwrap();
+    codeword[17] = !codeword[17];
+    assert_eq!(
+        code.decode(&codeword),
+        Ok(HammingDecodeResult::OneError {
+            position: 17,
+            decoded_bits: data
+        })
+    );
+    assert!(matches!(
+        code.decode(&codeword[1..]),
+        Err(HammingError::WrongCodewordLength { .. })
+    ));
+}
+
+#[test]
+fn commands_test() {
+    let mut raid_ii = RaidII::new(1024, true);
+    for line in ["write 'some text' notes", "corrupt 1", "checksum off"] {
+        let command = commands::parse(line).unwrap();
+        assert!(matches!(
+            commands::execute(&mut raid_ii, command),
+            Output::Text(_)
+        ));
+    }
+    let output = commands::execute(&mut raid_ii, commands::parse("read notes").unwrap());
+    assert!(output.to_string().contains("Content: some text"));
+    assert!(commands::parse("corrupt").is_err());
+}